@@ -0,0 +1,63 @@
+//! Lexes generated `.gem` files of growing size and checks that the time per
+//! byte stays flat, i.e. that lexing scales linearly. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use gemstone::Gemstone;
+
+const SIZES: &[usize] = &[1 << 20, 2 << 20, 4 << 20, 8 << 20];
+const RUNS: usize = 3;
+
+/// A chunk touching every kind of token, including non-ASCII text.
+const CHUNK: &str = r##"
+/// Documented with a `///` comment.
+class Größe {
+    private var wert: int = 0x7f_ff;
+    public function skaliert(faktor: float) -> float {
+        /* a /* nested */ block comment */
+        return faktor * 2.5e3 + 1.0f64; // trailing comment
+    }
+}
+
+function main() -> int {
+    var 名前: string = "héllo, 世界 \u{1F389}\n";
+    var raw: string = r#"C:\path"#;
+    for i in 0..10 {
+        if i % 3 == 0 && !(i >= 7) || i << 2 != 8 {
+            println('x');
+        }
+    }
+    return 0;
+}
+"##;
+
+fn generate(size: usize) -> String {
+    let mut source: String = String::with_capacity(size + CHUNK.len());
+    while source.len() < size {
+        source.push_str(CHUNK);
+    }
+    source
+}
+
+fn main() {
+    let mut nanos_per_byte: Vec<f64> = vec![];
+    for size in SIZES {
+        let source: String = generate(*size);
+        let mut gemstone: Gemstone = Gemstone::new();
+        let file: usize = gemstone.add_file("bench.gem", &source);
+        let mut best: Duration = Duration::MAX;
+        let mut tokens: usize = 0;
+        for _ in 0..RUNS {
+            let start: Instant = Instant::now();
+            tokens = gemstone.lex(file).expect("generated source should lex").len();
+            best = best.min(start.elapsed());
+        }
+        let per_byte: f64 = best.as_nanos() as f64 / source.len() as f64;
+        println!("{:>9} bytes  {:>9} tokens  {:>10.2?}  {:.2} ns/byte", source.len(), tokens, best, per_byte);
+        nanos_per_byte.push(per_byte);
+    }
+    // a quadratic lexer would take 8 times as long per byte on the largest input
+    let growth: f64 = nanos_per_byte[nanos_per_byte.len() - 1] / nanos_per_byte[0];
+    println!("time per byte grew {:.2}x from the smallest to the largest input", growth);
+    assert!(growth < 3.0, "lexing does not scale linearly");
+}
@@ -0,0 +1,350 @@
+use crate::diagnostics::Span;
+use crate::lexer::TokenKind;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    /// `int`, also spelled `i32`.
+    Int,
+    I8,
+    I16,
+    I64,
+    Isize,
+    U8,
+    U16,
+    U32,
+    U64,
+    Usize,
+    /// `float`, also spelled `f32`.
+    Float,
+    F64,
+    String,
+    Bool,
+    Void,
+    Char,
+    /// A class, with the type arguments of a generic class: `Box<int>`.
+    Class(String, Vec<Type>),
+    /// A type parameter of the generic class or function it appears in.
+    Parameter(String),
+    Pointer(Box<Type>),
+    /// `[T; N]`: `N` values of type `T`, stored inline.
+    Array(Box<Type>, u64),
+    /// `[T]`: a view of the elements of an array, which knows its length.
+    Slice(Box<Type>),
+    /// The type of `null`, which converts to every pointer type. It cannot be written in source.
+    Null,
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I64 => write!(f, "i64"),
+            Type::Isize => write!(f, "isize"),
+            Type::U8 => write!(f, "u8"),
+            Type::U16 => write!(f, "u16"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::Usize => write!(f, "usize"),
+            Type::Float => write!(f, "float"),
+            Type::F64 => write!(f, "f64"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Void => write!(f, "void"),
+            Type::Char => write!(f, "char"),
+            Type::Class(name, type_args) if type_args.is_empty() => write!(f, "{}", name),
+            Type::Class(name, type_args) => {
+                let type_args: Vec<String> = type_args.iter().map(|type_arg| type_arg.to_string()).collect();
+                write!(f, "{}<{}>", name, type_args.join(", "))
+            },
+            Type::Parameter(name) => write!(f, "{}", name),
+            Type::Pointer(inner) => write!(f, "{}*", inner),
+            Type::Array(inner, length) => write!(f, "[{}; {}]", inner, length),
+            Type::Slice(inner) => write!(f, "[{}]", inner),
+            Type::Null => write!(f, "null"),
+        }
+    }
+}
+
+impl Type {
+    /// The built-in type called `name`, which is also how literal suffixes name their type.
+    pub fn from_name(name: &str) -> Option<Type> {
+        Some(match name {
+            "int" | "i32" => Type::Int,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i64" => Type::I64,
+            "isize" => Type::Isize,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "usize" => Type::Usize,
+            "float" | "f32" => Type::Float,
+            "f64" => Type::F64,
+            "void" => Type::Void,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            "char" => Type::Char,
+            _ => return None,
+        })
+    }
+    /// The inclusive range of values an integer type can hold, or `None` for other types.
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        Some(match self {
+            Type::I8 => (i8::MIN as i128, i8::MAX as i128),
+            Type::I16 => (i16::MIN as i128, i16::MAX as i128),
+            Type::Int => (i32::MIN as i128, i32::MAX as i128),
+            Type::I64 | Type::Isize => (i64::MIN as i128, i64::MAX as i128),
+            Type::U8 => (0, u8::MAX as i128),
+            Type::U16 => (0, u16::MAX as i128),
+            Type::U32 => (0, u32::MAX as i128),
+            Type::U64 | Type::Usize => (0, u64::MAX as i128),
+            _ => return None,
+        })
+    }
+    pub fn is_integer(&self) -> bool {
+        self.integer_range().is_some()
+    }
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::Float | Type::F64)
+    }
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+    /// Replaces each of the type `parameters` with the matching type argument.
+    pub fn substitute(&self, parameters: &[String], type_args: &[Type]) -> Type {
+        match self {
+            Type::Parameter(name) => match parameters.iter().position(|parameter| parameter == name) {
+                Some(position) => type_args[position].clone(),
+                None => self.clone(),
+            },
+            Type::Class(name, args) => Type::Class(name.clone(), args.iter().map(|arg| arg.substitute(parameters, type_args)).collect()),
+            Type::Pointer(inner) => Type::Pointer(Box::new(inner.substitute(parameters, type_args))),
+            Type::Array(inner, length) => Type::Array(Box::new(inner.substitute(parameters, type_args)), *length),
+            Type::Slice(inner) => Type::Slice(Box::new(inner.substitute(parameters, type_args))),
+            _ => self.clone(),
+        }
+    }
+    pub fn has_parameters(&self) -> bool {
+        match self {
+            Type::Parameter(_) => true,
+            Type::Class(_, args) => args.iter().any(Type::has_parameters),
+            Type::Pointer(inner) | Type::Array(inner, _) | Type::Slice(inner) => inner.has_parameters(),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessModifier {
+    Public,
+    Private,
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub doc: Option<String>,
+    pub name: String,
+    /// The type parameters of a generic class, `T` in `class Box<T>`.
+    pub type_params: Vec<String>,
+    pub base_class: Option<String>,
+    /// The interfaces the class implements, `A` and `B` in `implements A, B`.
+    pub interfaces: Vec<String>,
+    pub methods: Vec<Expr>,
+}
+
+/// Methods that every class implementing the interface must define, lowered to
+/// a C++ class with only pure virtual methods.
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub doc: Option<String>,
+    pub name: String,
+    /// `ClassFunction`s without a body.
+    pub methods: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassFunction {
+    pub doc: Option<String>,
+    pub name: String,
+    pub args: Vec<Parameter>,
+    pub return_type: Type,
+    pub body: Box<Expr>,
+    pub is_virtual: bool,
+    pub is_override: bool,
+    pub is_external: bool,
+    pub access: AccessModifier,
+}
+
+/// A parameter of a function, method or constructor. Unless it is `anonymous`, an
+/// argument can also be passed to it by name: `f(1, label: 2)`. Parameters can
+/// only be assigned to when they are `mutable`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: Type,
+    pub is_anonymous: bool,
+    pub is_mutable: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassVariable {
+    pub doc: Option<String>,
+    pub name: String,
+    pub var_type: Type,
+    pub initializer: Box<Expr>,
+    pub access: AccessModifier,
+    /// Whether the generated constructor takes this field by name rather than by position.
+    pub is_named: bool,
+}
+
+/// A `constructor(...) { ... }` declared in a class body; a class may declare several.
+#[derive(Debug, Clone)]
+pub struct Constructor {
+    pub doc: Option<String>,
+    pub args: Vec<Parameter>,
+    pub body: Box<Expr>,
+    pub access: AccessModifier,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The `///` comments written above the item, one line each.
+    pub doc: Option<String>,
+    /// Set for out-of-line method definitions (`function Class::method()`).
+    pub class_name: Option<String>,
+    pub name: String,
+    pub type_params: Vec<String>,
+    pub args: Vec<Parameter>,
+    pub return_type: Type,
+    pub body: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub exprs: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionCall {
+    pub name: String,
+    /// The type arguments written out in the call, `max<int>(a, b)`; they are
+    /// inferred from the arguments when there are none.
+    pub type_args: Vec<Type>,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VariableDeclaration {
+    pub name: String,
+    pub value: Box<Expr>,
+    pub var_type: Type,
+    pub constant: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct If {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    /// Either a block or, for `else if`, another `If`.
+    pub else_branch: Option<Box<Expr>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct While {
+    pub condition: Box<Expr>,
+    pub body: Box<Expr>,
+}
+
+/// A C-style `for (init; condition; step)` loop; missing parts are `Empty`.
+#[derive(Debug, Clone)]
+pub struct For {
+    pub initializer: Box<Expr>,
+    pub condition: Box<Expr>,
+    pub step: Box<Expr>,
+    pub body: Box<Expr>,
+}
+
+/// A `for name in start..end` loop over a half-open integer range.
+#[derive(Debug, Clone)]
+pub struct ForRange {
+    pub variable: String,
+    pub start: Box<Expr>,
+    pub end: Box<Expr>,
+    pub body: Box<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct New {
+    pub class_name: String,
+    pub type_args: Vec<Type>,
+    pub args: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Class(Class),
+    Interface(Interface),
+    ClassFunction(ClassFunction),
+    ClassVariable(ClassVariable),
+    Constructor(Constructor),
+    Function(Function),
+    Block(Block),
+    FunctionCall(FunctionCall),
+    InternalFunctionCall(FunctionCall),
+    /// An integer literal and its type suffix, range checked by the type checker.
+    Int(u64, Option<String>),
+    Float(f64, Option<String>),
+    Bool(bool),
+    Char(char),
+    String(String),
+    Null,
+    /// An array literal, `[a, b, c]`.
+    Array(Vec<Expr>),
+    /// `[value; N]`: an array of `N` copies of `value`.
+    ArrayRepeat(Box<Expr>, u64),
+    Variable(String),
+    Return(Box<Expr>),
+    VariableDeclaration(VariableDeclaration),
+    New(New),
+    UnaryOp(TokenKind, Box<Expr>),
+    BinaryOp(Box<Expr>, TokenKind, Box<Expr>),
+    /// `value as T`: a numeric conversion or an upcast between class pointers.
+    Cast(Box<Expr>, Type),
+    /// `downcast<T>(pointer)`: a checked conversion to a `T*`, which is `null` when
+    /// the object is not a `T`.
+    Downcast(String, Box<Expr>),
+    /// `target = value`, or a compound assignment such as `target += value`.
+    Assignment(Box<Expr>, TokenKind, Box<Expr>),
+    Member(Box<Expr>, String),
+    /// A field reached through a pointer: `p->field`.
+    PointerMember(Box<Expr>, String),
+    MemberFunctionCall(Box<Expr>, FunctionCall),
+    /// A method called through a pointer: `p->method()`.
+    PointerMemberFunctionCall(Box<Expr>, FunctionCall),
+    /// `sequence[index]` on an array, a slice or a pointer.
+    Index(Box<Expr>, Box<Expr>),
+    /// A call argument passed by name, `label: value`.
+    NamedArgument(String, Box<Expr>),
+    If(If),
+    While(While),
+    For(For),
+    ForRange(ForRange),
+    Break,
+    Continue,
+    Empty,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Expr {
+        Expr { kind, span }
+    }
+}
@@ -0,0 +1,874 @@
+use std::collections::HashMap;
+
+use crate::ast::{AccessModifier, Block, Class, ClassFunction, ClassVariable, Constructor, Expr, ExprKind, For, ForRange, Function, FunctionCall, If, Interface, New, Parameter, Type, VariableDeclaration, While};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::lexer::TokenKind;
+use crate::typeck::argument_value;
+use crate::Gemstone;
+
+/// How many instances of generics a program can need when monomorphising.
+const MAX_INSTANCES: usize = 10000;
+
+/// The C++ side of arrays and slices: `[T]` is a pointer and a length, `[value; N]`
+/// fills a `std::array`, and checked indexing goes through `gem_at`.
+const SEQUENCE_PRELUDE: &str = r#"template <typename T> struct gem_slice {
+    T* data = nullptr;
+    std::size_t length = 0;
+    gem_slice() = default;
+    template <std::size_t N> gem_slice(std::array<T, N>& array) : data(array.data()), length(N) {}
+    std::size_t size() const { return length; }
+    T& operator[](std::size_t index) const { return data[index]; }
+};
+template <typename T, std::size_t N> std::array<T, N> gem_repeat(const T& value) {
+    std::array<T, N> array;
+    array.fill(value);
+    return array;
+}
+template <typename Sequence, typename Index> decltype(auto) gem_at(Sequence&& sequence, Index index, const char* location) {
+    if (index < 0 || static_cast<std::size_t>(index) >= std::size(sequence)) {
+        std::cerr << location << ": index " << +index << " is out of bounds for length " << std::size(sequence) << std::endl;
+        std::abort();
+    }
+    return sequence[index];
+}
+"#;
+
+impl Gemstone {
+    pub fn compile(&mut self, exprs: &[Expr]) -> Result<String, Vec<Diagnostic>> {
+        let mut output: String = String::new();
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        output.push_str("#include <cstddef>\n");
+        output.push_str("#include <cstdint>\n");
+        output.push_str("#include <iostream>\n");
+        output.push_str("#include <array>\n");
+        output.push_str("#include <cstdlib>\n");
+        output.push_str("#include <stdio.h>\n");
+        output.push_str("#include <string>\n");
+        // iostreams print `int8_t` and `uint8_t` as characters, so `print` goes through this
+        output.push_str("template <typename T> const T& gem_printable(const T& value) { return value; }\n");
+        output.push_str("inline int gem_printable(int8_t value) { return value; }\n");
+        output.push_str("inline unsigned gem_printable(uint8_t value) { return value; }\n");
+        output.push_str(SEQUENCE_PRELUDE);
+        self.instances.clear();
+        // out-of-line method definitions go last so every class they refer to is already complete
+        let (definitions, items): (Vec<&Expr>, Vec<&Expr>) = exprs.iter()
+            .partition(|expr| matches!(&expr.kind, ExprKind::Function(function) if function.class_name.is_some()));
+        let mut sections: Vec<String> = vec![];
+        for expr in items.iter().chain(&definitions) {
+            let generic: bool = match &expr.kind {
+                ExprKind::Class(class) => !class.type_params.is_empty(),
+                ExprKind::Function(function) => !function.type_params.is_empty(),
+                _ => false,
+            };
+            // when monomorphising, only the instances of generics are compiled
+            if generic && self.monomorphise {
+                sections.push(String::new());
+                continue;
+            }
+            match self.compile_statement(expr) {
+                Ok(code) => sections.push(code),
+                Err(diagnostic) => {
+                    diagnostics.push(diagnostic);
+                    sections.push(String::new());
+                },
+            }
+        }
+        if self.instances.is_empty() {
+            output.push_str(&sections.concat());
+        } else {
+            match self.compile_instances(&items, sections) {
+                Ok(code) => output.push_str(&code),
+                Err(diagnostic) => diagnostics.push(diagnostic),
+            }
+        }
+        if diagnostics.is_empty() {
+            Ok(output)
+        } else {
+            Err(diagnostics)
+        }
+    }
+    /// Compiles the instances of generics found while compiling the program, and
+    /// the ones they use in turn, around the compiled `sections` of the items. Every
+    /// class is declared up front, and a class instance is defined once its generic
+    /// class and the classes in its type arguments have been. The methods of class
+    /// instances, which may need other instances to be complete, and the function
+    /// instances are defined at the end.
+    fn compile_instances(&mut self, items: &[&Expr], sections: Vec<String>) -> Result<String, Diagnostic> {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut declarations: String = String::new();
+        for (position, item) in items.iter().enumerate() {
+            match &item.kind {
+                ExprKind::Class(class) => {
+                    positions.insert(class.name.clone(), position);
+                    if class.type_params.is_empty() {
+                        declarations.push_str(&format!("class {};\n", class.name));
+                    }
+                },
+                ExprKind::Interface(interface) => {
+                    positions.insert(interface.name.clone(), position);
+                    declarations.push_str(&format!("class {};\n", interface.name));
+                },
+                _ => {},
+            }
+        }
+        let mut class_instances: Vec<Vec<String>> = vec![vec![]; sections.len()];
+        let mut prototypes: String = String::new();
+        let mut definitions: String = String::new();
+        let mut next: usize = 0;
+        while next < self.instances.len() {
+            if next == MAX_INSTANCES {
+                return Err(Diagnostic::error(codes::INVALID_CODEGEN, Span::default(), format!("more than {} instances of generics are needed", MAX_INSTANCES))
+                    .with_note("a generic that uses itself with ever larger type arguments never runs out of instances"));
+            }
+            let (name, type_args): (String, Vec<Type>) = self.instances[next].clone();
+            let instance_name: String = self.instance_name(&name, &type_args);
+            next += 1;
+            if let Some(class) = self.classes.get(&name).cloned() {
+                self.substitution = (class.type_params.clone(), type_args.clone());
+                declarations.push_str(&format!("class {};\n", instance_name));
+                let instance: Class = Class { name: instance_name, type_params: vec![], ..class };
+                let code: Result<String, Diagnostic> = self.compile_class(&instance);
+                definitions.push_str(&self.compile_member_definitions(&instance)?);
+                let mut class_names: Vec<String> = vec![];
+                class_names_in(&Type::Class(name, type_args), &mut class_names);
+                let position: usize = class_names.iter().filter_map(|class_name| positions.get(class_name)).copied().max().unwrap_or(0);
+                class_instances[position].push(code?);
+            } else {
+                let function: Function = self.functions[&name].clone();
+                self.substitution = (function.type_params.clone(), type_args);
+                let function: Function = Function { name: instance_name, type_params: vec![], ..function };
+                prototypes.push_str(&format!("{};\n", self.compile_function_signature(&function)));
+                definitions.push_str(&self.compile_function(&function)?);
+            }
+            self.substitution = (vec![], vec![]);
+        }
+        let mut output: String = declarations;
+        output.push_str(&prototypes);
+        for (section, instances) in sections.into_iter().zip(class_instances) {
+            output.push_str(&section);
+            output.push_str(&instances.concat());
+        }
+        output.push_str(&definitions);
+        Ok(output)
+    }
+    /// Whether the class being compiled is an instance of a generic class, whose
+    /// constructors and methods are only declared in the class.
+    fn defines_members_out_of_line(&self) -> bool {
+        self.monomorphise && !self.substitution.0.is_empty()
+    }
+    /// The out-of-line definitions of the constructors and methods of `class`.
+    fn compile_member_definitions(&mut self, class: &Class) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        for member in &class.methods {
+            match &member.kind {
+                ExprKind::Constructor(constructor) => {
+                    let parameters: String = self.compile_parameters(&constructor.args);
+                    output.push_str(&format!("{0}::{0}({1}) ", class.name, parameters));
+                    output.push_str(&self.compile_expr(&constructor.body)?);
+                },
+                ExprKind::ClassFunction(class_function) if matches!(class_function.body.kind, ExprKind::Block(_)) => {
+                    let return_type: String = self.compile_type(&class_function.return_type);
+                    let parameters: String = self.compile_parameters(&class_function.args);
+                    output.push_str(&format!("{} {}::{}({}) ", return_type, class.name, class_function.name, parameters));
+                    output.push_str(&self.compile_expr(&class_function.body)?);
+                },
+                _ => {},
+            }
+        }
+        Ok(output)
+    }
+    /// The name of the copy of the generic `name` for `type_args`, such as
+    /// `Pair_int_float`, which is compiled later if it is new.
+    fn instance_name(&mut self, name: &str, type_args: &[Type]) -> String {
+        let mut instance_name: String = name.to_string();
+        for type_arg in type_args {
+            instance_name.push('_');
+            instance_name.push_str(&self.mangle_type(type_arg));
+        }
+        let instance: (String, Vec<Type>) = (name.to_string(), type_args.to_vec());
+        if !self.instances.contains(&instance) {
+            self.instances.push(instance);
+        }
+        instance_name
+    }
+    fn mangle_type(&mut self, type_: &Type) -> String {
+        match type_ {
+            Type::Class(class, type_args) if !type_args.is_empty() => self.instance_name(class, type_args),
+            Type::Pointer(inner) => format!("{}_ptr", self.mangle_type(inner)),
+            Type::Array(inner, length) => format!("array_{}_{}", self.mangle_type(inner), length),
+            Type::Slice(inner) => format!("slice_{}", self.mangle_type(inner)),
+            _ => type_.to_string(),
+        }
+    }
+    /// `template <typename T>` before a generic class or function compiled as a template.
+    fn compile_template_header(&self, type_params: &[String]) -> String {
+        if type_params.is_empty() {
+            return String::new();
+        }
+        let type_params: Vec<String> = type_params.iter().map(|type_param| format!("typename {}", type_param)).collect();
+        format!("template <{}>\n", type_params.join(", "))
+    }
+    /// The C++ type arguments of the call or `new` at `span` to a generic, or
+    /// nothing for one that isn't generic.
+    fn compile_generic_name(&mut self, span: Span, name: &str) -> String {
+        let type_args: Vec<Type> = match self.instantiations.get(&span) {
+            Some(type_args) => type_args.iter().map(|type_arg| type_arg.substitute(&self.substitution.0, &self.substitution.1)).collect(),
+            None => return name.to_string(),
+        };
+        if self.monomorphise {
+            return self.instance_name(name, &type_args);
+        }
+        let type_args: Vec<String> = type_args.iter().map(|type_arg| self.compile_type(type_arg)).collect();
+        format!("{}<{}>", name, type_args.join(", "))
+    }
+    fn compile_expr(&mut self, expr: &Expr) -> Result<String, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Class(class) => self.compile_class(class),
+            ExprKind::Interface(interface) => self.compile_interface(interface),
+            ExprKind::InternalFunctionCall(function_call) => self.compile_internal_function_call(expr.span, function_call),
+            ExprKind::String(string) => Ok(self.compile_string(string)),
+            ExprKind::Int(int, suffix) => Ok(self.compile_int(int, suffix)),
+            ExprKind::Float(float, _) => Ok(self.compile_float(float)),
+            ExprKind::Bool(bool) => Ok(bool.to_string()),
+            ExprKind::Char(c) => Ok(self.compile_char(c)),
+            ExprKind::Function(function) => self.compile_function(function),
+            ExprKind::Return(return_expr) => self.compile_return(return_expr),
+            ExprKind::VariableDeclaration(variable_declaration) => self.compile_variable_declaration(variable_declaration),
+            ExprKind::New(new) => self.compile_new(expr.span, new),
+            ExprKind::Variable(name) => Ok(name.clone()),
+            ExprKind::FunctionCall(function_call) => {
+                let name: String = self.compile_generic_name(expr.span, &function_call.name);
+                self.compile_call(&name, &function_call.args)
+            },
+            ExprKind::UnaryOp(op, operand) => {
+                let output: String = self.compile_unary_op(expr.span, op, operand)?;
+                Ok(self.compile_narrowing(expr.span, output))
+            },
+            ExprKind::BinaryOp(left, op, right) => {
+                let output: String = self.compile_binary_op(left, op, right)?;
+                Ok(self.compile_narrowing(expr.span, output))
+            },
+            ExprKind::Cast(value, target) => Ok(format!("static_cast<{}>({})", self.compile_type(target), self.compile_expr(value)?)),
+            ExprKind::Downcast(class_name, value) => Ok(format!("dynamic_cast<{}*>({})", class_name, self.compile_expr(value)?)),
+            ExprKind::Null => Ok("nullptr".to_string()),
+            ExprKind::Array(elements) => self.compile_array(expr.span, elements),
+            ExprKind::ArrayRepeat(value, length) => {
+                let element_type: String = self.compile_element_type(expr.span)?;
+                Ok(format!("gem_repeat<{}, {}>({})", element_type, length, self.compile_expr(value)?))
+            },
+            ExprKind::Index(sequence, position) => self.compile_index(sequence, position),
+            ExprKind::MemberFunctionCall(left, right) => self.compile_member_function_call(left, right, "."),
+            ExprKind::PointerMemberFunctionCall(left, right) => self.compile_member_function_call(left, right, "->"),
+            ExprKind::Member(left, name) => self.compile_member(left, name),
+            // the checker only accepts labels in declaration order, so the C++ call is positional
+            ExprKind::NamedArgument(_, value) => self.compile_expr(value),
+            ExprKind::PointerMember(left, name) => Ok(format!("{}->{}", self.compile_operand(left)?, name)),
+            ExprKind::Assignment(target, op, value) => self.compile_assignment(target, op, value),
+            ExprKind::Block(block) => self.compile_block(block),
+            ExprKind::If(if_expr) => self.compile_if(if_expr),
+            ExprKind::While(while_expr) => self.compile_while(while_expr),
+            ExprKind::For(for_expr) => self.compile_for(for_expr),
+            ExprKind::ForRange(for_range) => self.compile_for_range(expr.span, for_range),
+            ExprKind::Break => Ok("break;\n".to_string()),
+            ExprKind::Continue => Ok("continue;\n".to_string()),
+            ExprKind::Empty => Ok(String::new()),
+            _ => Err(Diagnostic::error(codes::INVALID_CODEGEN, expr.span, "this expression cannot appear here"))
+        }
+    }
+    /// Compiles `expr` in statement position, terminating bare expressions with `;`.
+    fn compile_statement(&mut self, expr: &Expr) -> Result<String, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Class(_) | ExprKind::Interface(_) | ExprKind::Function(_) | ExprKind::Block(_) | ExprKind::Return(_)
+                | ExprKind::VariableDeclaration(_) | ExprKind::If(_) | ExprKind::While(_) | ExprKind::For(_)
+                | ExprKind::ForRange(_) | ExprKind::Break | ExprKind::Continue | ExprKind::Empty => self.compile_expr(expr),
+            _ => Ok(format!("{};\n", self.compile_expr(expr)?)),
+        }
+    }
+    fn compile_class(&mut self, class: &Class) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+
+        let mut public_constructors: Vec<&Constructor> = vec![];
+        let mut private_constructors: Vec<&Constructor> = vec![];
+        let mut public_methods: Vec<&ClassFunction> = vec![];
+        let mut private_methods: Vec<&ClassFunction> = vec![];
+        let mut variables: Vec<&ClassVariable> = vec![];
+        let mut public_variables: Vec<&ClassVariable> = vec![];
+        for method in &class.methods {
+            match &method.kind {
+                ExprKind::Constructor(constructor) => {
+                    match constructor.access {
+                        AccessModifier::Public => public_constructors.push(constructor),
+                        AccessModifier::Private => private_constructors.push(constructor),
+                    }
+                }
+                ExprKind::ClassFunction(class_function) => {
+                    match class_function.access {
+                        AccessModifier::Public => public_methods.push(class_function),
+                        AccessModifier::Private => private_methods.push(class_function),
+                    }
+                }
+                ExprKind::ClassVariable(class_variable) => {
+                    match class_variable.access {
+                        AccessModifier::Public => public_variables.push(class_variable),
+                        AccessModifier::Private => variables.push(class_variable),
+                    }
+                }
+                _ => return Err(Diagnostic::error(codes::INVALID_CODEGEN, method.span, "expected a method or field declaration"))
+            }
+        }
+
+        output.push_str(&self.compile_doc(&class.doc));
+        output.push_str(&self.compile_template_header(&class.type_params));
+        output.push_str(&format!("class {}", class.name));
+        let bases: Vec<String> = class.base_class.iter().chain(&class.interfaces).map(|base| format!("public {}", base)).collect();
+        if !bases.is_empty() {
+            output.push_str(&format!(": {}", bases.join(", ")));
+        }
+        output.push_str(" {\n");
+        // the public section always exists, so `new` works even without public members
+        output.push_str("public:\n");
+        if public_constructors.is_empty() && private_constructors.is_empty() {
+            // a class without constructors gets one taking its public fields without a default, in order
+            let parameter_variables: Vec<&ClassVariable> = public_variables.iter()
+                .filter(|variable| matches!(variable.initializer.kind, ExprKind::Empty))
+                .copied()
+                .collect();
+            output.push_str(&format!("{}(", class.name));
+            let mut parameters: Vec<String> = vec![];
+            for variable in &parameter_variables {
+                parameters.push(format!("{} {}", self.compile_type(&variable.var_type), variable.name));
+            }
+            output.push_str(&parameters.join(", "));
+            output.push(')');
+            let mut initializers: Vec<String> = vec![];
+            for variable in &parameter_variables {
+                initializers.push(format!("{}({})", variable.name, variable.name));
+            }
+            if !initializers.is_empty() {
+                output.push_str(&format!(" : {}", initializers.join(", ")));
+            }
+            output.push_str(" {}\n");
+        }
+        for constructor in public_constructors {
+            output.push_str(&self.compile_constructor(&class.name, constructor)?);
+        }
+        for method in public_methods {
+            output.push_str(&self.compile_class_function(method)?);
+        }
+        output.push_str(&self.compile_forwarding_overrides(class));
+        for variable in public_variables {
+            output.push_str(&self.compile_class_variable(variable)?);
+        }
+        if !private_constructors.is_empty() || !private_methods.is_empty() || !variables.is_empty() {
+            output.push_str("private:\n");
+        }
+        for constructor in private_constructors {
+            output.push_str(&self.compile_constructor(&class.name, constructor)?);
+        }
+        for method in private_methods {
+            output.push_str(&self.compile_class_function(method)?);
+        }
+        for variable in variables {
+            output.push_str(&self.compile_class_variable(variable)?);
+        }
+        output.push_str("};\n");
+        Ok(output)
+    }
+
+    /// An interface method the class inherits from its base instead of declaring
+    /// it would stay pure virtual in C++, so it gets an override calling the base.
+    fn compile_forwarding_overrides(&mut self, class: &Class) -> String {
+        let base_class: &String = match &class.base_class {
+            Some(base_class) => base_class,
+            None => return String::new(),
+        };
+        let mut output: String = String::new();
+        let mut forwarded: Vec<String> = vec![];
+        for interface_name in &class.interfaces {
+            let methods: Vec<Expr> = self.interfaces.get(interface_name).map(|interface| interface.methods.clone()).unwrap_or_default();
+            for member in &methods {
+                let method: &ClassFunction = match &member.kind {
+                    ExprKind::ClassFunction(method) => method,
+                    _ => continue,
+                };
+                let declared: bool = class.methods.iter().any(|member| matches!(&member.kind, ExprKind::ClassFunction(class_function) if class_function.name == method.name));
+                if declared || forwarded.contains(&method.name) {
+                    continue;
+                }
+                forwarded.push(method.name.clone());
+                let args: Vec<&str> = method.args.iter().map(|arg| arg.name.as_str()).collect();
+                output.push_str(&format!("{} {}({}) override {{ return {}::{}({}); }}\n", self.compile_type(&method.return_type), method.name,
+                    self.compile_parameters(&method.args), base_class, method.name, args.join(", ")));
+            }
+        }
+        output
+    }
+    /// An interface is a class with only pure virtual methods, and a virtual
+    /// destructor so objects can be deleted through it.
+    fn compile_interface(&mut self, interface: &Interface) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_doc(&interface.doc));
+        output.push_str(&format!("class {} {{\npublic:\n", interface.name));
+        output.push_str(&format!("virtual ~{}() = default;\n", interface.name));
+        for member in &interface.methods {
+            match &member.kind {
+                ExprKind::ClassFunction(method) => output.push_str(&self.compile_class_function(method)?),
+                _ => return Err(Diagnostic::error(codes::INVALID_CODEGEN, member.span, "expected a method declaration")),
+            }
+        }
+        output.push_str("};\n");
+        Ok(output)
+    }
+    /// Carries `///` comments over to the C++ so tools like Doxygen can read them there too.
+    fn compile_doc(&self, doc: &Option<String>) -> String {
+        match doc {
+            Some(doc) => doc.lines().map(|line| format!("/// {}\n", line).replace("/// \n", "///\n")).collect(),
+            None => String::new(),
+        }
+    }
+    /// Defaults become C++ default member initializers, which every constructor applies.
+    fn compile_class_variable(&mut self, variable: &ClassVariable) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_doc(&variable.doc));
+        output.push_str(&format!("{} {}", self.compile_type(&variable.var_type), variable.name));
+        if !matches!(variable.initializer.kind, ExprKind::Empty) {
+            output.push_str(&format!(" = {}", self.compile_expr(&variable.initializer)?));
+        }
+        output.push_str(";\n");
+        Ok(output)
+    }
+    fn compile_constructor(&mut self, class_name: &str, constructor: &Constructor) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_doc(&constructor.doc));
+        output.push_str(&format!("{}({}) ", class_name, self.compile_parameters(&constructor.args)));
+        if self.defines_members_out_of_line() {
+            output.replace_range(output.len() - 1.., ";\n");
+            return Ok(output);
+        }
+        match &constructor.body.kind {
+            ExprKind::Block(block) => output.push_str(&self.compile_block(block)?),
+            _ => return Err(Diagnostic::error(codes::MISSING_BODY, constructor.body.span, "constructor must have a body")),
+        }
+        Ok(output)
+    }
+    fn compile_class_function(&mut self, class_function: &ClassFunction) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_doc(&class_function.doc));
+        if class_function.is_virtual {
+            output.push_str("virtual ");
+        }
+
+        output.push_str(&format!("{} {}(", self.compile_type(&class_function.return_type), class_function.name));
+
+        let args: String = self.compile_parameters(&class_function.args);
+
+        output.push_str(&args);
+        output.push_str(") ");
+        if class_function.is_override {
+            output.push_str("override ");
+        }
+        match &class_function.body.kind {
+            ExprKind::Block(_) if self.defines_members_out_of_line() => output.push_str(";\n"),
+            ExprKind::Block(block) => output.push_str(&self.compile_block(block)?),
+            ExprKind::Empty => {
+                if class_function.is_override {
+                    return Err(Diagnostic::error(codes::MISSING_BODY, class_function.body.span, "override function must have a body"));
+                }
+                if class_function.is_virtual {
+                    output.push_str("= 0;\n");
+                } else if class_function.is_external {
+                    output.push_str(";\n");
+                } else {
+                    return Err(Diagnostic::error(codes::MISSING_BODY, class_function.body.span, format!("method `{}` must have a body", class_function.name)));
+                }
+                return Ok(output);
+            },
+            _ => return Err(Diagnostic::error(codes::INVALID_CODEGEN, class_function.body.span, "expected a block or `;`"))
+        }
+
+        Ok(output)
+    }
+    fn compile_function(&mut self, function: &Function) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_doc(&function.doc));
+        output.push_str(&self.compile_template_header(&function.type_params));
+        output.push_str(&self.compile_function_signature(function));
+        output.push(' ');
+        output.push_str(&self.compile_block(match &function.body.kind {
+            ExprKind::Block(block) => block,
+            _ => return Err(Diagnostic::error(codes::MISSING_BODY, function.body.span, format!("function `{}` must have a body", function.name)))
+        })?);
+
+        Ok(output)
+    }
+    fn compile_function_signature(&mut self, function: &Function) -> String {
+        let name: String = match &function.class_name {
+            Some(class_name) => format!("{}::{}", class_name, function.name),
+            None => function.name.clone(),
+        };
+        format!("{} {}({})", self.compile_type(&function.return_type), name, self.compile_parameters(&function.args))
+    }
+    fn compile_parameters(&mut self, args: &[Parameter]) -> String {
+        let mut parameters: Vec<String> = vec![];
+        for arg in args {
+            // `T const x` makes a pointer parameter itself constant, not what it points to
+            let qualifier: &str = if arg.is_mutable || holds_class_value(&arg.param_type) { "" } else { " const" };
+            parameters.push(format!("{}{} {}", self.compile_type(&arg.param_type), qualifier, arg.name));
+        }
+        parameters.join(", ")
+    }
+    fn compile_return(&mut self, return_expr: &Expr) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str("return");
+        if !matches!(return_expr.kind, ExprKind::Empty) {
+            output.push_str(&format!(" {}", self.compile_expr(return_expr)?));
+        }
+        output.push_str(";\n");
+        Ok(output)
+    }
+    fn compile_variable_declaration(&mut self, variable_declaration: &VariableDeclaration) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&format!("{} ", self.compile_type(&variable_declaration.var_type)));
+        if variable_declaration.constant && !holds_class_value(&variable_declaration.var_type) {
+            output.push_str("const ")
+        }
+        output.push_str(&format!("{} = ", variable_declaration.name));
+        output.push_str(&self.compile_expr(&variable_declaration.value)?);
+        output.push_str(";\n");
+        Ok(output)
+    }
+    fn compile_new(&mut self, span: Span, new: &New) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        let class_name: String = self.compile_generic_name(span, &new.class_name);
+        if !matches!(self.expr_types.get(&span), Some(Type::Class(..))) {
+            output.push_str("new ");
+        }
+        output.push_str(&format!("{}(", class_name));
+        let mut args: Vec<String> = vec![];
+        for arg in &new.args {
+            let value: String = self.compile_expr(arg)?;
+            args.push(match self.argument_conversions.get(&argument_value(arg).span).cloned() {
+                Some(parameter_type) => format!("static_cast<{}>({})", self.compile_type(&parameter_type), value),
+                None => value,
+            });
+        }
+        let args: String = args.join(", ");
+        output.push_str(&args);
+        output.push(')');
+        Ok(output)
+    }
+    fn compile_type(&mut self, type_: &Type) -> String {
+        match type_ {
+            Type::Int => "int".to_string(),
+            Type::I8 => "int8_t".to_string(),
+            Type::I16 => "int16_t".to_string(),
+            Type::I64 => "int64_t".to_string(),
+            Type::Isize => "std::ptrdiff_t".to_string(),
+            Type::U8 => "uint8_t".to_string(),
+            Type::U16 => "uint16_t".to_string(),
+            Type::U32 => "uint32_t".to_string(),
+            Type::U64 => "uint64_t".to_string(),
+            Type::Usize => "std::size_t".to_string(),
+            Type::Float => "float".to_string(),
+            Type::F64 => "double".to_string(),
+            Type::String => "std::string".to_string(),
+            Type::Bool => "bool".to_string(),
+            Type::Void => "void".to_string(),
+            Type::Char => "char".to_string(),
+            Type::Class(class, type_args) if type_args.is_empty() => class.clone(),
+            Type::Class(class, type_args) => {
+                let type_args: Vec<Type> = type_args.iter().map(|type_arg| type_arg.substitute(&self.substitution.0, &self.substitution.1)).collect();
+                if self.monomorphise {
+                    return self.instance_name(class, &type_args);
+                }
+                let type_args: Vec<String> = type_args.iter().map(|type_arg| self.compile_type(type_arg)).collect();
+                format!("{}<{}>", class, type_args.join(", "))
+            },
+            Type::Parameter(name) => match self.substitution.0.iter().position(|type_param| type_param == name) {
+                Some(position) => {
+                    let type_arg: Type = self.substitution.1[position].clone();
+                    self.compile_type(&type_arg)
+                },
+                None => name.clone(),
+            },
+            Type::Pointer(pointer) => format!("{}*", self.compile_type(pointer)),
+            Type::Array(inner, length) => format!("std::array<{}, {}>", self.compile_type(inner), length),
+            Type::Slice(inner) => format!("gem_slice<{}>", self.compile_type(inner)),
+            Type::Null => "std::nullptr_t".to_string(),
+        }
+    }
+    fn compile_block(&mut self, block: &Block) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str("{\n");
+        for expr in &block.exprs {
+            output.push_str(&self.compile_statement(expr)?);
+        }
+        output.push_str("}\n");
+        Ok(output)
+    }
+    fn compile_if(&mut self, if_expr: &If) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&format!("if ({}) ", self.compile_expr(&if_expr.condition)?));
+        output.push_str(&self.compile_expr(&if_expr.then_branch)?);
+        if let Some(else_branch) = &if_expr.else_branch {
+            output.push_str("else ");
+            output.push_str(&self.compile_expr(else_branch)?);
+        }
+        Ok(output)
+    }
+    fn compile_while(&mut self, while_expr: &While) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&format!("while ({}) ", self.compile_expr(&while_expr.condition)?));
+        output.push_str(&self.compile_expr(&while_expr.body)?);
+        Ok(output)
+    }
+    fn compile_for(&mut self, for_expr: &For) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        // the initializer is compiled as a statement, so it brings its own `;`
+        let initializer: String = match &for_expr.initializer.kind {
+            ExprKind::Empty => ";".to_string(),
+            _ => self.compile_statement(&for_expr.initializer)?.trim_end().to_string(),
+        };
+        output.push_str(&format!("for ({} {}; {}) ", initializer, self.compile_expr(&for_expr.condition)?, self.compile_expr(&for_expr.step)?));
+        output.push_str(&self.compile_expr(&for_expr.body)?);
+        Ok(output)
+    }
+    fn compile_for_range(&mut self, span: Span, for_range: &ForRange) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        let variable_type: Type = self.range_types.get(&span).cloned().unwrap_or(Type::Int);
+        let variable_type: String = self.compile_type(&variable_type);
+        output.push_str(&format!("for ({0} {1} = {2}, {1}_end = {3}; {1} < {1}_end; {1}++) ", variable_type, for_range.variable, self.compile_expr(&for_range.start)?, self.compile_expr(&for_range.end)?));
+        output.push_str(&self.compile_expr(&for_range.body)?);
+        Ok(output)
+    }
+    fn compile_internal_function_call(&mut self, span: Span, function_call: &FunctionCall) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        if function_call.args.len() != 1 {
+            return Err(Diagnostic::error(codes::INVALID_INTERNAL_CALL, span, format!("`{}` takes exactly one argument, got {}", function_call.name, function_call.args.len())));
+        }
+        match function_call.name.as_str() {
+            "print" => {
+                output.push_str(&format!("std::cout << gem_printable({})", self.compile_expr(&function_call.args[0])?));
+            }
+            "println" => {
+                output.push_str(&format!("std::cout << gem_printable({}) << std::endl", self.compile_expr(&function_call.args[0])?));
+            }
+            _ => return Err(Diagnostic::error(codes::INVALID_INTERNAL_CALL, span, format!("unknown internal function `{}`", function_call.name)))
+        }
+        Ok(output)
+    }
+    fn compile_call(&mut self, name: &str, call_args: &[Expr]) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&format!("{}(", name));
+        let mut args: Vec<String> = vec![];
+        for arg in call_args {
+            args.push(self.compile_expr(arg)?);
+        }
+        let args: String = args.join(", ");
+        output.push_str(&args);
+        output.push(')');
+        Ok(output)
+    }
+    /// Re-escapes a decoded string for a C++ literal. Non-ASCII text is kept as UTF-8;
+    /// other control characters use three-digit octal escapes so a following digit
+    /// can't be read as part of the escape.
+    fn compile_string(&mut self, string: &str) -> String {
+        let mut output: String = String::from("\"");
+        for c in string.chars() {
+            match c {
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                '\t' => output.push_str("\\t"),
+                '\r' => output.push_str("\\r"),
+                c if c.is_ascii_control() => output.push_str(&format!("\\{:03o}", c as u32)),
+                c => output.push(c),
+            }
+        }
+        output.push('"');
+        // a plain literal would end the `std::string` at the first NUL
+        if string.contains('\0') {
+            return format!("std::string({}, {})", output, string.len());
+        }
+        output
+    }
+    fn compile_int(&mut self, int: &u64, suffix: &Option<String>) -> String {
+        // C++ only gives a literal an unsigned type when asked to
+        if *int > i64::MAX as u64 {
+            format!("{}ull", int)
+        } else if suffix.as_deref().is_some_and(|suffix| suffix.starts_with('u')) {
+            format!("{}u", int)
+        } else {
+            format!("{}", int)
+        }
+    }
+    fn compile_float(&mut self, float: &f64) -> String {
+        // `{:?}` keeps the `.0` on whole numbers and prints enough digits to round-trip
+        format!("{:?}", float)
+    }
+    fn compile_char(&mut self, c: &char) -> String {
+        match c {
+            '\n' => "'\\n'".to_string(),
+            '\t' => "'\\t'".to_string(),
+            '\r' => "'\\r'".to_string(),
+            '\0' => "'\\0'".to_string(),
+            '\\' => "'\\\\'".to_string(),
+            '\'' => "'\\''".to_string(),
+            c if c.is_ascii_graphic() || *c == ' ' => format!("'{}'", c),
+            c => format!("'\\x{:02x}'", *c as u32),
+        }
+    }
+    fn compile_binary_op(&mut self, lhs: &Expr, op: &TokenKind, rhs: &Expr) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_binary_operand(lhs, op)?);
+        output.push_str(&format!(" {} ", match self.compile_operator(op) {
+            Some(op) => op,
+            None => return Err(Diagnostic::error(codes::INVALID_CODEGEN, lhs.span.to(&rhs.span), format!("invalid binary operator {}", op)))
+        }));
+        output.push_str(&self.compile_binary_operand(rhs, op)?);
+        Ok(output)
+    }
+    /// A string literal is a `const char*` in C++, which `+` can't concatenate and
+    /// the comparisons would compare by address, so it becomes a `std::string`.
+    fn compile_binary_operand(&mut self, operand: &Expr, op: &TokenKind) -> Result<String, Diagnostic> {
+        let on_text: bool = matches!(op, TokenKind::Plus | TokenKind::EqualEqual | TokenKind::BangEqual | TokenKind::Less | TokenKind::LessEqual | TokenKind::Greater | TokenKind::GreaterEqual);
+        match &operand.kind {
+            ExprKind::String(string) if on_text && !string.contains('\0') => Ok(format!("std::string({})", self.compile_string(string))),
+            _ => self.compile_operand(operand),
+        }
+    }
+    fn compile_assignment(&mut self, target: &Expr, op: &TokenKind, value: &Expr) -> Result<String, Diagnostic> {
+        let op: &str = match op {
+            TokenKind::Equal => "=",
+            TokenKind::PlusEqual => "+=",
+            TokenKind::MinusEqual => "-=",
+            TokenKind::StarEqual => "*=",
+            TokenKind::SlashEqual => "/=",
+            _ => return Err(Diagnostic::error(codes::INVALID_CODEGEN, target.span.to(&value.span), format!("invalid assignment operator {}", op))),
+        };
+        // nothing binds looser than assignment, so the value never needs parentheses
+        Ok(format!("{} {} {}", self.compile_expr(target)?, op, self.compile_expr(value)?))
+    }
+    fn compile_unary_op(&mut self, span: Span, op: &TokenKind, operand: &Expr) -> Result<String, Diagnostic> {
+        match (op, self.compile_operator(op)) {
+            (TokenKind::Bang | TokenKind::Minus | TokenKind::Tilde, Some(op)) => Ok(format!("{}{}", op, self.compile_operand(operand)?)),
+            _ => Err(Diagnostic::error(codes::INVALID_CODEGEN, span, format!("invalid unary operator {}", op))),
+        }
+    }
+    /// C++ promotes operands narrower than `int` before applying an operator, so the
+    /// result is cast back to keep `~x` of a `u8` a `u8`. A type parameter might
+    /// stand for one of those types.
+    fn compile_narrowing(&mut self, span: Span, output: String) -> String {
+        match self.expr_types.get(&span).map(|result_type| result_type.substitute(&self.substitution.0, &self.substitution.1)) {
+            Some(result_type @ (Type::I8 | Type::I16 | Type::U8 | Type::U16 | Type::Parameter(_))) => format!("static_cast<{}>({})", self.compile_type(&result_type), output),
+            _ => output,
+        }
+    }
+    /// Every Gemstone operator is spelled the same in C++.
+    fn compile_operator(&self, op: &TokenKind) -> Option<&'static str> {
+        Some(match op {
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::EqualEqual => "==",
+            TokenKind::BangEqual => "!=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::AndAnd => "&&",
+            TokenKind::PipePipe => "||",
+            TokenKind::Ampersand => "&",
+            TokenKind::Pipe => "|",
+            TokenKind::Caret => "^",
+            TokenKind::ShiftLeft => "<<",
+            TokenKind::ShiftRight => ">>",
+            TokenKind::Bang => "!",
+            TokenKind::Tilde => "~",
+            _ => return None,
+        })
+    }
+    /// Parenthesizes nested operations so the C++ keeps the grouping of the syntax tree.
+    fn compile_operand(&mut self, expr: &Expr) -> Result<String, Diagnostic> {
+        match &expr.kind {
+            // a unary operand is wrapped too, so `-(-x)` doesn't turn into `--x`, and so is
+            // `new`, which would otherwise swallow a following `->`
+            ExprKind::BinaryOp(..) | ExprKind::UnaryOp(..) | ExprKind::Assignment(..) | ExprKind::New(_) => Ok(format!("({})", self.compile_expr(expr)?)),
+            _ => self.compile_expr(expr),
+        }
+    }
+    fn compile_member_function_call(&mut self, left: &Expr, function_call: &FunctionCall, operator: &str) -> Result<String, Diagnostic> {
+        let mut output: String = String::new();
+        output.push_str(&self.compile_operand(left)?);
+        output.push_str(operator);
+        output.push_str(&self.compile_call(&function_call.name, &function_call.args)?);
+        Ok(output)
+    }
+    fn compile_array(&mut self, span: Span, elements: &[Expr]) -> Result<String, Diagnostic> {
+        let element_type: String = self.compile_element_type(span)?;
+        let length: usize = elements.len();
+        let mut values: Vec<String> = vec![];
+        for element in elements {
+            let value: String = self.compile_expr(element)?;
+            // braced initializers reject the implicit conversions Gemstone allows, like `int` to `float`
+            let found: Option<String> = self.expr_types.get(&element.span).cloned().map(|found| self.compile_type(&found));
+            values.push(match found {
+                Some(found) if found != element_type => format!("static_cast<{}>({})", element_type, value),
+                _ => value,
+            });
+        }
+        Ok(format!("std::array<{}, {}>{{{}}}", element_type, length, values.join(", ")))
+    }
+    /// The C++ element type of the array literal at `span`, as recorded by the type checker.
+    fn compile_element_type(&mut self, span: Span) -> Result<String, Diagnostic> {
+        match self.expr_types.get(&span).cloned() {
+            Some(Type::Array(inner, _)) => Ok(self.compile_type(&inner)),
+            _ => Err(Diagnostic::error(codes::INVALID_CODEGEN, span, "the type of this array is unknown")),
+        }
+    }
+    /// Arrays and slices are indexed through `gem_at`, which aborts with the source
+    /// location on an index out of bounds, unless bounds checks are turned off.
+    fn compile_index(&mut self, sequence: &Expr, position: &Expr) -> Result<String, Diagnostic> {
+        let checked: bool = self.bounds_checks && matches!(self.expr_types.get(&sequence.span), Some(Type::Array(..) | Type::Slice(_)));
+        if checked {
+            let location: String = format!("{}:{}:{}", self.files[position.span.file].name, position.span.line, position.span.column);
+            return Ok(format!("gem_at({}, {}, {})", self.compile_expr(sequence)?, self.compile_expr(position)?, self.compile_string(&location)));
+        }
+        Ok(format!("{}[{}]", self.compile_operand(sequence)?, self.compile_expr(position)?))
+    }
+    fn compile_member(&mut self, left: &Expr, member: &String) -> Result<String, Diagnostic> {
+        if let Some(Type::Array(..) | Type::Slice(_)) = self.expr_types.get(&left.span) {
+            return Ok(format!("std::size({})", self.compile_expr(left)?));
+        }
+        let mut output: String = String::new();
+        output.push_str(&self.compile_expr(left)?);
+        output.push_str(&format!(".{}", member));
+        Ok(output)
+    }
+}
+
+/// Adds the name of every class `type_` refers to, generic or not, to `class_names`.
+fn class_names_in(type_: &Type, class_names: &mut Vec<String>) {
+    match type_ {
+        Type::Class(class, type_args) => {
+            class_names.push(class.clone());
+            for type_arg in type_args {
+                class_names_in(type_arg, class_names);
+            }
+        },
+        Type::Pointer(inner) | Type::Array(inner, _) | Type::Slice(inner) => class_names_in(inner, class_names),
+        _ => {},
+    }
+}
+
+/// Whether a value of `type_` holds an object directly. Those are never made `const`
+/// in C++, because Gemstone methods are not `const` and could not be called on it.
+fn holds_class_value(type_: &Type) -> bool {
+    match type_ {
+        Type::Class(..) => true,
+        Type::Array(inner, _) => holds_class_value(inner),
+        _ => false,
+    }
+}
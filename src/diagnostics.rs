@@ -0,0 +1,137 @@
+use colored::Colorize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub file: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Span {
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: self.file,
+            line: self.line,
+            column: self.column,
+            end_line: other.end_line,
+            end_column: other.end_column,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub contents: String,
+}
+
+/// Error codes attached to every diagnostic, grouped by the stage that emits them.
+pub mod codes {
+    // lexer
+    pub const INVALID_CHARACTER: &str = "E0001";
+    pub const INVALID_NUMBER: &str = "E0002";
+    pub const INVALID_CHAR_LITERAL: &str = "E0003";
+    pub const UNTERMINATED_LITERAL: &str = "E0004";
+    pub const INVALID_ESCAPE: &str = "E0005";
+    // parser
+    pub const UNEXPECTED_TOKEN: &str = "E0100";
+    pub const CONFLICTING_MODIFIERS: &str = "E0101";
+    pub const EXTERNAL_WITH_BODY: &str = "E0102";
+    pub const UNUSED_DOC_COMMENT: &str = "W0100";
+    // type checker
+    pub const MISMATCHED_TYPES: &str = "E0200";
+    pub const UNKNOWN_CLASS: &str = "E0201";
+    pub const UNSUPPORTED_EXPRESSION: &str = "E0202";
+    pub const UNDEFINED_NAME: &str = "E0203";
+    pub const WRONG_ARGUMENT_COUNT: &str = "E0204";
+    pub const UNKNOWN_MEMBER: &str = "E0205";
+    pub const PRIVATE_MEMBER: &str = "E0206";
+    pub const INVALID_OPERANDS: &str = "E0207";
+    pub const INVALID_OVERRIDE: &str = "E0208";
+    pub const INVALID_DEFINITION: &str = "E0209";
+    pub const MISSING_DEFINITION: &str = "E0210";
+    pub const ASSIGN_TO_CONSTANT: &str = "E0211";
+    pub const INVALID_ASSIGNMENT: &str = "E0212";
+    pub const LITERAL_OUT_OF_RANGE: &str = "E0213";
+    pub const NO_MATCHING_CONSTRUCTOR: &str = "E0214";
+    pub const INVALID_LABEL: &str = "E0215";
+    pub const UNINITIALIZED_FIELD: &str = "E0216";
+    pub const INVALID_CAST: &str = "E0217";
+    pub const INDEX_OUT_OF_BOUNDS: &str = "E0218";
+    pub const INVALID_TYPE_ARGUMENT: &str = "E0219";
+    pub const INVALID_IMPLEMENTATION: &str = "E0220";
+    // name resolution
+    pub const DUPLICATE_DECLARATION: &str = "E0250";
+    pub const OUTSIDE_LOOP: &str = "E0251";
+    pub const SHADOWED_NAME: &str = "W0250";
+    // codegen
+    pub const INVALID_CODEGEN: &str = "E0300";
+    pub const MISSING_BODY: &str = "E0301";
+    pub const INVALID_INTERNAL_CALL: &str = "E0302";
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Span,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code, message: message.into(), span, notes: vec![] }
+    }
+    pub fn warning(code: &'static str, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, code, message: message.into(), span, notes: vec![] }
+    }
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+    pub fn render(&self, files: &[SourceFile]) -> String {
+        let file: &SourceFile = &files[self.span.file];
+        let line: &str = file.contents.lines().nth(self.span.line.saturating_sub(1)).unwrap_or("");
+        let line_number: String = self.span.line.to_string();
+        let gutter: String = " ".repeat(line_number.len());
+        let start: usize = self.span.column.saturating_sub(1);
+        let end: usize = if self.span.end_line == self.span.line {
+            self.span.end_column.saturating_sub(1)
+        } else {
+            line.chars().count()
+        };
+        // keep tabs in the padding so the carets line up with the source line
+        let padding: String = line.chars().take(start).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let underline: String = "^".repeat(end.saturating_sub(start).max(1));
+
+        let (label, underline) = match self.severity {
+            Severity::Error => (format!("error[{}]", self.code).red().bold(), underline.red().bold()),
+            Severity::Warning => (format!("warning[{}]", self.code).yellow().bold(), underline.yellow().bold()),
+            Severity::Note => ("note".cyan().bold(), underline.cyan().bold()),
+        };
+
+        let mut output: String = String::new();
+        output.push_str(&format!("{}: {}\n", label, self.message.bold()));
+        output.push_str(&format!("{}{} {}:{}:{}\n", gutter, "-->".blue().bold(), file.name, self.span.line, self.span.column));
+        output.push_str(&format!("{} {}\n", gutter, "|".blue().bold()));
+        output.push_str(&format!("{} {} {}\n", line_number.blue().bold(), "|".blue().bold(), line));
+        output.push_str(&format!("{} {} {}{}\n", gutter, "|".blue().bold(), padding, underline));
+        for note in &self.notes {
+            output.push_str(&format!("{} {} {}: {}\n", gutter, "=".blue().bold(), "note".bold(), note));
+        }
+        output
+    }
+}
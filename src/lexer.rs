@@ -0,0 +1,588 @@
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::Gemstone;
+use unicode_xid::UnicodeXID;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Word(String),
+    LeftParen,
+    RightParen,
+    LeftArrow, // ->
+    LeftCurly,
+    RightCurly,
+    LeftBracket,
+    RightBracket,
+    String(String),
+    Semicolon,
+    Colon,
+    DoubleColon, // ::
+    Comma,
+    /// An integer literal and its type suffix, if any (`255u8`).
+    Int(u64, Option<String>),
+    Float(f64, Option<String>),
+    Char(char),
+    Equal,
+    EqualEqual, // ==
+    Bang,
+    BangEqual, // !=
+    Less,
+    LessEqual, // <=
+    Greater,
+    GreaterEqual, // >=
+    AndAnd, // &&
+    PipePipe, // ||
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft, // <<
+    ShiftRight, // >>
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    PlusEqual, // +=
+    MinusEqual, // -=
+    StarEqual, // *=
+    SlashEqual, // /=
+    Dot,
+    DotDot, // ..
+    /// The text of a `///` comment, without the slashes.
+    DocComment(String),
+    Eof,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TokenKind::Word(word) => write!(f, "`{}`", word),
+            TokenKind::LeftParen => write!(f, "`(`"),
+            TokenKind::RightParen => write!(f, "`)`"),
+            TokenKind::LeftArrow => write!(f, "`->`"),
+            TokenKind::LeftCurly => write!(f, "`{{`"),
+            TokenKind::RightCurly => write!(f, "`}}`"),
+            TokenKind::LeftBracket => write!(f, "`[`"),
+            TokenKind::RightBracket => write!(f, "`]`"),
+            TokenKind::String(string) => write!(f, "string {:?}", string),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::DoubleColon => write!(f, "`::`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Int(int, suffix) => write!(f, "integer `{}{}`", int, suffix.as_deref().unwrap_or("")),
+            TokenKind::Float(float, suffix) => write!(f, "float `{}{}`", float, suffix.as_deref().unwrap_or("")),
+            TokenKind::Char(c) => write!(f, "character `{}`", c.escape_default()),
+            TokenKind::Equal => write!(f, "`=`"),
+            TokenKind::EqualEqual => write!(f, "`==`"),
+            TokenKind::Bang => write!(f, "`!`"),
+            TokenKind::BangEqual => write!(f, "`!=`"),
+            TokenKind::Less => write!(f, "`<`"),
+            TokenKind::LessEqual => write!(f, "`<=`"),
+            TokenKind::Greater => write!(f, "`>`"),
+            TokenKind::GreaterEqual => write!(f, "`>=`"),
+            TokenKind::AndAnd => write!(f, "`&&`"),
+            TokenKind::PipePipe => write!(f, "`||`"),
+            TokenKind::Ampersand => write!(f, "`&`"),
+            TokenKind::Pipe => write!(f, "`|`"),
+            TokenKind::Caret => write!(f, "`^`"),
+            TokenKind::Tilde => write!(f, "`~`"),
+            TokenKind::ShiftLeft => write!(f, "`<<`"),
+            TokenKind::ShiftRight => write!(f, "`>>`"),
+            TokenKind::Plus => write!(f, "`+`"),
+            TokenKind::Minus => write!(f, "`-`"),
+            TokenKind::Star => write!(f, "`*`"),
+            TokenKind::Slash => write!(f, "`/`"),
+            TokenKind::Percent => write!(f, "`%`"),
+            TokenKind::PlusEqual => write!(f, "`+=`"),
+            TokenKind::MinusEqual => write!(f, "`-=`"),
+            TokenKind::StarEqual => write!(f, "`*=`"),
+            TokenKind::SlashEqual => write!(f, "`/=`"),
+            TokenKind::Dot => write!(f, "`.`"),
+            TokenKind::DotDot => write!(f, "`..`"),
+            TokenKind::DocComment(_) => write!(f, "doc comment"),
+            TokenKind::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Turns the result of a literal helper into a token, recording its error to be
+/// reported once the span of the whole literal is known.
+fn literal(result: Result<TokenKind, (&'static str, String)>, error: &mut Option<(&'static str, String)>) -> TokenKind {
+    result.unwrap_or_else(|failure| {
+        *error = Some(failure);
+        TokenKind::Eof
+    })
+}
+
+/// Walks the source one `char` at a time, keeping track of the line and column
+/// of the next character. Every step is constant time, so lexing is linear in
+/// the size of the file.
+struct Cursor<'a> {
+    source: &'a str,
+    /// Byte offset of the next character.
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(source: &'a str) -> Cursor<'a> {
+        // editors on Windows like to start UTF-8 files with a byte order mark
+        let source: &str = source.strip_prefix('\u{feff}').unwrap_or(source);
+        Cursor { source, offset: 0, line: 1, column: 1 }
+    }
+    fn peek(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+    /// Looks `n` characters past the next one without consuming anything.
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.offset..].chars().nth(n)
+    }
+    fn bump(&mut self) -> Option<char> {
+        let c: char = self.peek()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+    /// Consumes the next character if it is `expected`.
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+    /// Consumes characters for as long as `predicate` holds and returns them.
+    fn bump_while(&mut self, predicate: impl Fn(char) -> bool) -> &'a str {
+        let start: usize = self.offset;
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
+        &self.source[start..self.offset]
+    }
+}
+
+impl Gemstone {
+    pub fn lex(&mut self, file: usize) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let contents: String = self.files[file].contents.clone();
+        let mut cursor: Cursor = Cursor::new(&contents);
+        let mut tokens: Vec<Token> = vec![];
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+
+        while let Some(c) = cursor.peek() {
+            let mut error: Option<(&'static str, String)> = None;
+            let mut is_comment: bool = false;
+            let start_line: usize = cursor.line;
+            let start_column: usize = cursor.column;
+            let kind: TokenKind = match c {
+                ' ' | '\t' | '\r' | '\n' => { cursor.bump(); continue; }
+                '(' => { cursor.bump(); TokenKind::LeftParen }
+                ')' => { cursor.bump(); TokenKind::RightParen }
+                '[' => { cursor.bump(); TokenKind::LeftBracket }
+                ']' => { cursor.bump(); TokenKind::RightBracket }
+                '-' => {
+                    cursor.bump();
+                    if cursor.eat('>') {
+                        TokenKind::LeftArrow
+                    } else if cursor.eat('=') {
+                        TokenKind::MinusEqual
+                    } else {
+                        TokenKind::Minus
+                    }
+                }
+                '{' => { cursor.bump(); TokenKind::LeftCurly }
+                '}' => { cursor.bump(); TokenKind::RightCurly }
+                ';' => { cursor.bump(); TokenKind::Semicolon }
+                ':' => {
+                    cursor.bump();
+                    if cursor.eat(':') {
+                        TokenKind::DoubleColon
+                    } else {
+                        TokenKind::Colon
+                    }
+                }
+                ',' => { cursor.bump(); TokenKind::Comma }
+                '=' | '!' | '<' | '>' | '&' | '|' => {
+                    let second: Option<char> = cursor.peek_nth(1);
+                    let (kind, length): (TokenKind, usize) = match (c, second) {
+                        ('=', Some('=')) => (TokenKind::EqualEqual, 2),
+                        ('=', _) => (TokenKind::Equal, 1),
+                        ('!', Some('=')) => (TokenKind::BangEqual, 2),
+                        ('!', _) => (TokenKind::Bang, 1),
+                        ('<', Some('=')) => (TokenKind::LessEqual, 2),
+                        ('<', Some('<')) => (TokenKind::ShiftLeft, 2),
+                        ('<', _) => (TokenKind::Less, 1),
+                        ('>', Some('=')) => (TokenKind::GreaterEqual, 2),
+                        ('>', Some('>')) => (TokenKind::ShiftRight, 2),
+                        ('>', _) => (TokenKind::Greater, 1),
+                        ('&', Some('&')) => (TokenKind::AndAnd, 2),
+                        ('&', _) => (TokenKind::Ampersand, 1),
+                        ('|', Some('|')) => (TokenKind::PipePipe, 2),
+                        _ => (TokenKind::Pipe, 1),
+                    };
+                    for _ in 0..length {
+                        cursor.bump();
+                    }
+                    kind
+                }
+                '/' if cursor.peek_nth(1) == Some('/') => {
+                    let comment: &str = cursor.bump_while(|c| c != '\n');
+                    // `///` documents the next item, but `////` is an ordinary comment
+                    match comment.strip_prefix("///") {
+                        Some(doc) if !doc.starts_with('/') => TokenKind::DocComment(doc.strip_prefix(' ').unwrap_or(doc).trim_end().to_string()),
+                        _ => {
+                            is_comment = true;
+                            TokenKind::Eof
+                        }
+                    }
+                }
+                '/' if cursor.peek_nth(1) == Some('*') => {
+                    // block comments nest, so a commented-out region may itself contain comments
+                    let mut depth: usize = 0;
+                    loop {
+                        match (cursor.peek(), cursor.peek_nth(1)) {
+                            (Some('/'), Some('*')) => {
+                                depth += 1;
+                                cursor.bump();
+                                cursor.bump();
+                            },
+                            (Some('*'), Some('/')) => {
+                                depth -= 1;
+                                cursor.bump();
+                                cursor.bump();
+                                if depth == 0 {
+                                    break;
+                                }
+                            },
+                            (Some(_), _) => {
+                                cursor.bump();
+                            },
+                            (None, _) => {
+                                error = Some((codes::UNTERMINATED_LITERAL, "block comment is never terminated".to_string()));
+                                break;
+                            },
+                        }
+                    }
+                    is_comment = true;
+                    TokenKind::Eof
+                }
+                '+' | '*' | '/' => {
+                    cursor.bump();
+                    match (c, cursor.eat('=')) {
+                        ('+', false) => TokenKind::Plus,
+                        ('+', true) => TokenKind::PlusEqual,
+                        ('*', false) => TokenKind::Star,
+                        ('*', true) => TokenKind::StarEqual,
+                        (_, false) => TokenKind::Slash,
+                        (_, true) => TokenKind::SlashEqual,
+                    }
+                }
+                '%' => { cursor.bump(); TokenKind::Percent }
+                '^' => { cursor.bump(); TokenKind::Caret }
+                '~' => { cursor.bump(); TokenKind::Tilde }
+                '.' => {
+                    cursor.bump();
+                    if cursor.eat('.') {
+                        TokenKind::DotDot
+                    } else {
+                        TokenKind::Dot
+                    }
+                }
+                // raw strings: r"..." and r#"..."#
+                'r' if matches!(cursor.peek_nth(1), Some('"' | '#')) => literal(lex_string(&mut cursor), &mut error),
+                // identifiers follow Unicode's UAX #31, like Rust's
+                c if c == '_' || c.is_xid_start() => TokenKind::Word(cursor.bump_while(UnicodeXID::is_xid_continue).to_string()),
+                '"' => literal(lex_string(&mut cursor), &mut error),
+                '\'' => literal(lex_char(&mut cursor), &mut error),
+                '0'..='9' => literal(lex_number(&mut cursor), &mut error),
+                c => {
+                    let span: Span = Span { file, line: start_line, column: start_column, end_line: start_line, end_column: start_column + 1 };
+                    diagnostics.push(Diagnostic::error(codes::INVALID_CHARACTER, span, format!("invalid character: '{}' (U+{:04X})", c.escape_debug(), c as u32)));
+                    cursor.bump();
+                    continue;
+                }
+            };
+            let span: Span = Span { file, line: start_line, column: start_column, end_line: cursor.line, end_column: cursor.column };
+            if let Some((code, message)) = error {
+                diagnostics.push(Diagnostic::error(code, span, message));
+                continue;
+            }
+            if is_comment {
+                continue;
+            }
+            tokens.push(Token { kind, span });
+        }
+        tokens.push(Token {
+            kind: TokenKind::Eof,
+            span: Span { file, line: cursor.line, column: cursor.column, end_line: cursor.line, end_column: cursor.column },
+        });
+
+        if diagnostics.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(diagnostics)
+        }
+    }
+}
+
+const INT_SUFFIXES: &[&str] = &["i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize"];
+const FLOAT_SUFFIXES: &[&str] = &["f32", "f64"];
+
+/// Lexes a number literal: decimal, `0x` hex, `0o` octal or `0b` binary digits
+/// with optional `_` separators, a fraction and exponent for decimal floats, and
+/// an optional type suffix.
+fn lex_number(cursor: &mut Cursor) -> Result<TokenKind, (&'static str, String)> {
+    let radix: u32 = match (cursor.peek(), cursor.peek_nth(1)) {
+        (Some('0'), Some('x')) => 16,
+        (Some('0'), Some('o')) => 8,
+        (Some('0'), Some('b')) => 2,
+        _ => 10,
+    };
+    if radix != 10 {
+        cursor.bump();
+        cursor.bump();
+    }
+    let mut digits: String = String::new();
+    while let Some(c) = cursor.peek() {
+        if c == '_' {
+            cursor.bump();
+        } else if c.is_digit(radix) || (radix != 16 && c.is_ascii_digit()) {
+            digits.push(c);
+            cursor.bump();
+        } else {
+            break;
+        }
+    }
+    let mut is_float: bool = false;
+    if radix == 10 {
+        // `0..10` is a range, not the float `0.` followed by `.10`
+        if cursor.peek() == Some('.') && cursor.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            digits.push('.');
+            cursor.bump();
+            digits.extend(cursor.bump_while(|c| c.is_ascii_digit() || c == '_').chars().filter(|c| *c != '_'));
+        }
+        let sign: usize = if matches!(cursor.peek_nth(1), Some('+' | '-')) { 1 } else { 0 };
+        if matches!(cursor.peek(), Some('e' | 'E')) && cursor.peek_nth(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            for _ in 0..=sign {
+                digits.extend(cursor.bump());
+            }
+            digits.push_str(cursor.bump_while(|c| c.is_ascii_digit()));
+        }
+    }
+    let suffix: &str = cursor.bump_while(UnicodeXID::is_xid_continue);
+    let suffix: Option<String> = if suffix.is_empty() { None } else { Some(suffix.to_string()) };
+
+    if let Some(invalid) = digits.chars().find(|c| c.is_ascii_digit() && !c.is_digit(radix)) {
+        return Err((codes::INVALID_NUMBER, format!("invalid digit `{}` in base {} literal", invalid, radix)));
+    }
+    if digits.is_empty() {
+        return Err((codes::INVALID_NUMBER, "number literal has no digits".to_string()));
+    }
+    match suffix.as_deref() {
+        None => {},
+        Some(suffix) if FLOAT_SUFFIXES.contains(&suffix) && radix == 10 => is_float = true,
+        Some(suffix) if INT_SUFFIXES.contains(&suffix) && !is_float => {},
+        Some(suffix) => return Err((codes::INVALID_NUMBER, format!("invalid suffix `{}` for {} literal", suffix, if is_float { "float" } else { "number" }))),
+    }
+    if is_float {
+        return digits.parse::<f64>().map(|float| TokenKind::Float(float, suffix)).map_err(|_| (codes::INVALID_NUMBER, format!("invalid float literal `{}`", digits)));
+    }
+    match u64::from_str_radix(&digits, radix) {
+        Ok(int) => Ok(TokenKind::Int(int, suffix)),
+        Err(_) => Err((codes::INVALID_NUMBER, "integer literal is too large".to_string())),
+    }
+}
+
+/// Lexes a character literal such as `'a'` or `'\n'` starting at the opening quote.
+fn lex_char(cursor: &mut Cursor) -> Result<TokenKind, (&'static str, String)> {
+    cursor.bump(); // skip '
+    let raw: String = match lex_quoted(cursor, '\'') {
+        Some(raw) => raw,
+        None => return Err((codes::UNTERMINATED_LITERAL, "character literal is never terminated".to_string())),
+    };
+    let decoded: String = decode_escapes(&raw)?;
+    let mut chars = decoded.chars();
+    let c: char = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        (None, _) => return Err((codes::INVALID_CHAR_LITERAL, "character literal is empty".to_string())),
+        (Some(_), Some(_)) => return Err((codes::INVALID_CHAR_LITERAL, "character literal may only contain one character".to_string())),
+    };
+    // `char` is a single byte in the generated C++
+    if !c.is_ascii() {
+        return Err((codes::INVALID_CHAR_LITERAL, format!("character literal `{}` does not fit in a `char`", c)));
+    }
+    Ok(TokenKind::Char(c))
+}
+
+/// Lexes a string literal starting at its opening quote (or the `r` of a raw
+/// string). Plain strings end at the line; `"""` strings may span lines and
+/// have their common indentation removed; raw strings keep every character as
+/// written, and `r#"..."#` may contain `"`.
+fn lex_string(cursor: &mut Cursor) -> Result<TokenKind, (&'static str, String)> {
+    if cursor.eat('r') {
+        let mut hashes: usize = 0;
+        while cursor.eat('#') {
+            hashes += 1;
+        }
+        if !cursor.eat('"') {
+            return Err((codes::INVALID_CHARACTER, "expected `\"` to start the raw string".to_string()));
+        }
+        let mut string: String = String::new();
+        loop {
+            match cursor.peek() {
+                None => return Err((codes::UNTERMINATED_LITERAL, "raw string is never terminated".to_string())),
+                Some('"') if (1..=hashes).all(|offset| cursor.peek_nth(offset) == Some('#')) => {
+                    for _ in 0..=hashes {
+                        cursor.bump();
+                    }
+                    return Ok(TokenKind::String(string));
+                },
+                Some(c) => {
+                    string.push(c);
+                    cursor.bump();
+                },
+            }
+        }
+    }
+    if cursor.peek_nth(1) == Some('"') && cursor.peek_nth(2) == Some('"') {
+        for _ in 0..3 {
+            cursor.bump();
+        }
+        let mut raw: String = String::new();
+        loop {
+            match cursor.peek() {
+                None => return Err((codes::UNTERMINATED_LITERAL, "multiline string is never terminated".to_string())),
+                Some('"') if cursor.peek_nth(1) == Some('"') && cursor.peek_nth(2) == Some('"') => {
+                    for _ in 0..3 {
+                        cursor.bump();
+                    }
+                    break;
+                },
+                Some('\\') => {
+                    cursor.bump();
+                    raw.push('\\');
+                    raw.extend(cursor.bump());
+                },
+                Some(c) => {
+                    raw.push(c);
+                    cursor.bump();
+                },
+            }
+        }
+        return Ok(TokenKind::String(decode_escapes(&dedent(&raw))?));
+    }
+    cursor.bump(); // skip "
+    match lex_quoted(cursor, '"') {
+        Some(raw) => Ok(TokenKind::String(decode_escapes(&raw)?)),
+        None => Err((codes::UNTERMINATED_LITERAL, "string is never terminated".to_string())),
+    }
+}
+
+/// Collects the text up to an unescaped `quote` on the same line, leaving escapes
+/// undecoded, and moves past the closing quote. Returns `None` when the line or
+/// file ends first.
+fn lex_quoted(cursor: &mut Cursor, quote: char) -> Option<String> {
+    let mut raw: String = String::new();
+    loop {
+        match cursor.peek() {
+            None | Some('\n') => return None,
+            Some(c) if c == quote => {
+                cursor.bump();
+                return Some(raw);
+            },
+            Some('\\') if cursor.peek_nth(1).is_some_and(|c| c != '\n') => {
+                cursor.bump();
+                raw.push('\\');
+                raw.extend(cursor.bump());
+            },
+            Some(c) => {
+                raw.push(c);
+                cursor.bump();
+            },
+        }
+    }
+}
+
+/// Drops the line break after an opening `"""` and the indentation shared by every
+/// non-blank line, so a multiline string can be indented along with the code.
+fn dedent(raw: &str) -> String {
+    let raw: &str = raw.strip_prefix('\n').unwrap_or(raw);
+    // the closing `"""` on its own line sets no content, only indentation
+    let raw: &str = match raw.rfind('\n') {
+        Some(last) if raw[last + 1..].trim().is_empty() => &raw[..=last],
+        _ => raw,
+    };
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let indent: usize = raw.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(indentation)
+        .min()
+        .unwrap_or(0);
+    let mut string: String = String::new();
+    for line in raw.split_inclusive('\n') {
+        // blank lines may be indented less than the rest
+        string.push_str(&line[indent.min(indentation(line))..]);
+    }
+    string
+}
+
+/// Decodes `\n`, `\t`, `\r`, `\0`, `\\`, `\"`, `\'` and `\u{...}` escapes.
+fn decode_escapes(raw: &str) -> Result<String, (&'static str, String)> {
+    let mut decoded: String = String::new();
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        decoded.push(match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => {
+                let invalid = || (codes::INVALID_ESCAPE, "unicode escapes are written `\\u{XXXX}` with 1 to 6 hex digits".to_string());
+                if chars.next() != Some('{') {
+                    return Err(invalid());
+                }
+                let mut digits: String = String::new();
+                let mut closed: bool = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    digits.push(c);
+                }
+                if !closed {
+                    return Err((codes::INVALID_ESCAPE, "unicode escape is missing its closing `}`".to_string()));
+                }
+                if digits.is_empty() || digits.len() > 6 {
+                    return Err(invalid());
+                }
+                let value: u32 = u32::from_str_radix(&digits, 16).map_err(|_| invalid())?;
+                char::from_u32(value).ok_or_else(|| (codes::INVALID_ESCAPE, format!("`\\u{{{}}}` is not a valid unicode character", digits)))?
+            },
+            Some(c) => return Err((codes::INVALID_ESCAPE, format!("unknown escape `\\{}`", c))),
+            None => return Err((codes::INVALID_ESCAPE, "escape at the end of the literal".to_string())),
+        });
+    }
+    Ok(decoded)
+}
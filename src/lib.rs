@@ -0,0 +1,131 @@
+//! The Gemstone compiler: lexing, parsing, type checking and C++ code generation.
+//!
+//! Every stage is a method on [`Gemstone`], which owns the loaded source files and
+//! the state shared between stages:
+//!
+//! ```no_run
+//! let mut gemstone = gemstone::Gemstone::new();
+//! let file = gemstone.add_file("main.gem", "function main() -> int { return 0; }");
+//! let output = gemstone.lex(file)
+//!     .and_then(|tokens| gemstone.parse(&tokens))
+//!     .and_then(|exprs| gemstone.compile(&exprs));
+//! ```
+
+use std::collections::HashMap;
+
+pub mod ast;
+pub mod codegen;
+pub mod diagnostics;
+pub mod lexer;
+pub mod parser;
+pub mod resolve;
+pub mod typeck;
+
+use ast::{Class, Function, Interface, Type};
+use diagnostics::{Diagnostic, SourceFile, Span};
+use resolve::{Symbol, SymbolTable};
+use typeck::TypeParameterUse;
+
+#[derive(Debug, Clone)]
+pub struct Gemstone {
+    files: Vec<SourceFile>,
+    internal_functions: Vec<String>,
+    classes: HashMap<String, Class>,
+    interfaces: HashMap<String, Interface>,
+    functions: HashMap<String, Function>,
+    /// The type parameters of the generic class or function being parsed.
+    type_parameters: Vec<String>,
+    /// Set when the first `>` of a `>>` token has closed a list of type arguments
+    /// and the second is still to be consumed.
+    pending_greater: bool,
+    symbols: SymbolTable,
+    bindings: HashMap<Span, Symbol>,
+    /// The types of the expressions whose C++ depends on them, by span: operators,
+    /// array literals and the operands of indexing and `.length`.
+    expr_types: HashMap<Span, Type>,
+    /// The parameter types that arguments of overloaded constructors are converted
+    /// to before the call, by the span of the argument.
+    argument_conversions: HashMap<Span, Type>,
+    /// The type of the variable of each range loop, taken from its bounds, by the
+    /// span of the loop.
+    range_types: HashMap<Span, Type>,
+    /// The generic class or function whose body is being checked.
+    generic_owner: Option<String>,
+    /// What the body of each generic class and function does with its type parameters.
+    type_parameter_uses: HashMap<String, Vec<TypeParameterUse>>,
+    /// The type arguments of calls and `new` expressions of generics, by span.
+    instantiations: HashMap<Span, Vec<Type>>,
+    /// The type arguments to check against the uses of their type parameter once
+    /// every body has been checked: the generic, the type parameter, the type
+    /// argument and where it was given.
+    requirement_checks: Vec<(String, String, Type, Span)>,
+    /// Whether indexing an array or a slice checks the index at run time.
+    bounds_checks: bool,
+    /// Whether generics become a copy per type arguments instead of C++ templates.
+    monomorphise: bool,
+    /// The type parameters of the generic instance being compiled, and their type arguments.
+    substitution: (Vec<String>, Vec<Type>),
+    /// The instances of generics found while compiling, by name and type arguments.
+    instances: Vec<(String, Vec<Type>)>,
+    return_type: Type,
+    current_class: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Default for Gemstone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gemstone {
+    pub fn new() -> Gemstone {
+        Gemstone {
+            files: vec![],
+            internal_functions: vec![
+                "print".to_string(),
+                "println".to_string(),
+            ],
+            classes: HashMap::new(),
+            interfaces: HashMap::new(),
+            functions: HashMap::new(),
+            type_parameters: vec![],
+            pending_greater: false,
+            symbols: SymbolTable::new(),
+            bindings: HashMap::new(),
+            expr_types: HashMap::new(),
+            argument_conversions: HashMap::new(),
+            range_types: HashMap::new(),
+            generic_owner: None,
+            type_parameter_uses: HashMap::new(),
+            instantiations: HashMap::new(),
+            requirement_checks: vec![],
+            bounds_checks: true,
+            monomorphise: false,
+            substitution: (vec![], vec![]),
+            instances: vec![],
+            return_type: Type::Void,
+            current_class: None,
+            diagnostics: vec![],
+            warnings: vec![],
+        }
+    }
+    pub fn add_file(&mut self, name: &str, contents: &str) -> usize {
+        self.files.push(SourceFile { name: name.to_string(), contents: contents.to_string() });
+        self.files.len() - 1
+    }
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+    pub fn set_bounds_checks(&mut self, bounds_checks: bool) {
+        self.bounds_checks = bounds_checks;
+    }
+    pub fn set_monomorphise(&mut self, monomorphise: bool) {
+        self.monomorphise = monomorphise;
+    }
+    /// Returns the warnings collected by the stages run so far, leaving none behind.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+}
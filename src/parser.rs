@@ -0,0 +1,933 @@
+use crate::ast::{AccessModifier, Block, Class, ClassFunction, ClassVariable, Constructor, Expr, ExprKind, For, ForRange, Function, FunctionCall, If, Interface, New, Parameter, Type, VariableDeclaration, While};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::lexer::{Token, TokenKind};
+use crate::Gemstone;
+
+/// Binary operators and their precedence, loosest first. Unlike C, bitwise
+/// operators bind tighter than comparisons, so `x & 1 == 0` means `(x & 1) == 0`.
+const BINARY_OPERATORS: &[(TokenKind, u8)] = &[
+    (TokenKind::PipePipe, 1),
+    (TokenKind::AndAnd, 2),
+    (TokenKind::EqualEqual, 3),
+    (TokenKind::BangEqual, 3),
+    (TokenKind::Less, 3),
+    (TokenKind::LessEqual, 3),
+    (TokenKind::Greater, 3),
+    (TokenKind::GreaterEqual, 3),
+    (TokenKind::Pipe, 4),
+    (TokenKind::Caret, 5),
+    (TokenKind::Ampersand, 6),
+    (TokenKind::ShiftLeft, 7),
+    (TokenKind::ShiftRight, 7),
+    (TokenKind::Plus, 8),
+    (TokenKind::Minus, 8),
+    (TokenKind::Star, 9),
+    (TokenKind::Slash, 9),
+    (TokenKind::Percent, 9),
+];
+
+fn binary_precedence(kind: &TokenKind) -> Option<u8> {
+    BINARY_OPERATORS.iter().find(|(op, _)| op == kind).map(|(_, precedence)| *precedence)
+}
+
+impl Gemstone {
+    pub fn parse(&mut self, tokens: &[Token]) -> Result<Vec<Expr>, Vec<Diagnostic>> {
+        let (exprs, diagnostics) = self.parse_partial(tokens);
+        if diagnostics.is_empty() {
+            Ok(exprs)
+        } else {
+            Err(diagnostics)
+        }
+    }
+    /// Parses as much of the file as possible, returning every item that parsed
+    /// alongside all syntax errors encountered on the way.
+    pub fn parse_partial(&mut self, tokens: &[Token]) -> (Vec<Expr>, Vec<Diagnostic>) {
+        let mut exprs: Vec<Expr> = vec![];
+        let mut index: usize = 0;
+
+        while tokens[index].kind != TokenKind::Eof {
+            let start: usize = index;
+            match self.parse_token(tokens, &mut index) {
+                Ok(expr) => exprs.push(expr),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(tokens, &mut index, start, false);
+                }
+            }
+        }
+
+        (exprs, std::mem::take(&mut self.diagnostics))
+    }
+    /// Skips tokens after a syntax error until a point where parsing can resume:
+    /// just past a `;`, at a closing `}` (consumed at the top level, left for the
+    /// enclosing block otherwise), or at a `class`/`function` keyword.
+    fn synchronize(&mut self, tokens: &[Token], index: &mut usize, start: usize, in_block: bool) {
+        self.pending_greater = false;
+        if *index == start {
+            *index += 1;
+        }
+        let mut depth: usize = 0;
+        loop {
+            match &tokens[*index].kind {
+                TokenKind::Eof => return,
+                TokenKind::LeftCurly => depth += 1,
+                TokenKind::RightCurly if depth > 0 => depth -= 1,
+                TokenKind::RightCurly => {
+                    if !in_block {
+                        *index += 1;
+                    }
+                    return;
+                }
+                TokenKind::Semicolon if depth == 0 => {
+                    *index += 1;
+                    return;
+                }
+                TokenKind::Word(word) if depth == 0 && (word == "class" || word == "interface" || word == "function") => return,
+                _ => {}
+            }
+            *index += 1;
+        }
+    }
+    fn parse_token(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        match tokens[*index].kind.clone() {
+            TokenKind::DocComment(_) => self.parse_documented(tokens, index, Self::parse_token),
+            TokenKind::Word(word) => {
+                match word.as_str() {
+                    "class" => self.parse_class_def(tokens, index),
+                    "interface" => self.parse_interface_def(tokens, index),
+                    "function" => self.parse_function(tokens, index),
+                    "return" => self.parse_return(tokens, index),
+                    "var" => self.parse_variable(tokens, index, false),
+                    "const" => self.parse_variable(tokens, index, true),
+                    "if" => self.parse_if(tokens, index),
+                    "while" => self.parse_while(tokens, index),
+                    "for" => self.parse_for(tokens, index),
+                    "break" | "continue" => {
+                        let start: Span = tokens[*index].span;
+                        *index += 1;
+                        self._match(tokens, index, &TokenKind::Semicolon)?;
+                        let kind: ExprKind = if word == "break" { ExprKind::Break } else { ExprKind::Continue };
+                        Ok(Expr::new(kind, self.span_from(tokens, start, *index)))
+                    },
+                    _ => self.parse_expression_statement(tokens, index),
+                }
+            }
+            TokenKind::LeftCurly => self.parse_block(tokens, index),
+            TokenKind::Semicolon => {
+                let span: Span = tokens[*index].span;
+                *index += 1;
+                Ok(Expr::new(ExprKind::Empty, span))
+            },
+            _ => self.parse_expression_statement(tokens, index)
+        }
+    }
+    /// Parses the `///` comments at `index` and the item after them with `parse_item`,
+    /// attaching the comments to it. Comments above anything that can't carry
+    /// documentation are reported as a warning and dropped.
+    fn parse_documented(&mut self, tokens: &[Token], index: &mut usize, parse_item: fn(&mut Self, &[Token], &mut usize) -> Result<Expr, Diagnostic>) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        let mut lines: Vec<String> = vec![];
+        while let TokenKind::DocComment(line) = &tokens[*index].kind {
+            lines.push(line.clone());
+            *index += 1;
+        }
+        let span: Span = start.to(&tokens[*index - 1].span);
+        let doc: Option<String> = Some(lines.join("\n"));
+        let mut expr: Expr = match tokens[*index].kind {
+            TokenKind::RightCurly | TokenKind::Eof => Expr::new(ExprKind::Empty, span),
+            _ => parse_item(self, tokens, index)?,
+        };
+        match &mut expr.kind {
+            ExprKind::Class(class) => {
+                class.doc = doc;
+                self.classes.insert(class.name.clone(), class.clone());
+            },
+            ExprKind::Interface(interface) => {
+                interface.doc = doc;
+                self.interfaces.insert(interface.name.clone(), interface.clone());
+            },
+            ExprKind::Function(function) => function.doc = doc,
+            ExprKind::ClassFunction(class_function) => class_function.doc = doc,
+            ExprKind::ClassVariable(class_variable) => class_variable.doc = doc,
+            ExprKind::Constructor(constructor) => constructor.doc = doc,
+            _ => self.warnings.push(Diagnostic::warning(codes::UNUSED_DOC_COMMENT, span, "doc comment does not document anything")
+                .with_note("doc comments go above a class, interface, function, method, constructor or field; use `//` for other comments")),
+        }
+        Ok(expr)
+    }
+    fn parse_expression_statement(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let expr: Expr = self.parse_expression(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Semicolon)?;
+        Ok(expr)
+    }
+    fn parse_expression(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        self.parse_assignment(tokens, index)
+    }
+    /// Assignment binds loosest and groups to the right: `a = b = c` is `a = (b = c)`.
+    fn parse_assignment(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let target: Expr = self.parse_binary(tokens, index, 1)?;
+        match tokens[*index].kind {
+            TokenKind::Equal | TokenKind::PlusEqual | TokenKind::MinusEqual | TokenKind::StarEqual | TokenKind::SlashEqual => {
+                let op: TokenKind = tokens[*index].kind.clone();
+                *index += 1;
+                let value: Expr = self.parse_assignment(tokens, index)?;
+                let span: Span = target.span.to(&value.span);
+                Ok(Expr::new(ExprKind::Assignment(Box::new(target), op, Box::new(value)), span))
+            },
+            _ => Ok(target),
+        }
+    }
+    fn parse_class_def(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1;
+        let name: String = self.parse_word(tokens, index)?;
+        let type_params: Vec<String> = self.parse_type_parameters(tokens, index)?;
+        let mut base_class: Option<String> = None;
+        if tokens[*index].kind == TokenKind::Colon {
+            *index += 1;
+            base_class = Some(self.parse_word(tokens, index)?);
+        }
+        let mut interfaces: Vec<String> = vec![];
+        if self._is(tokens, index, &TokenKind::Word("implements".to_string())) {
+            loop {
+                interfaces.push(self.parse_word(tokens, index)?);
+                if !self._is(tokens, index, &TokenKind::Comma) {
+                    break;
+                }
+            }
+        }
+        let mut methods: Vec<Expr> = vec![];
+        self._match(tokens, index, &TokenKind::LeftCurly)?;
+        self.type_parameters = type_params.clone();
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightCurly {
+            let member: usize = *index;
+            match self.parse_class_member(tokens, index) {
+                // a trailing doc comment leaves nothing behind but its warning
+                Ok(method) if matches!(method.kind, ExprKind::Empty) => {},
+                Ok(method) => methods.push(method),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(tokens, index, member, true);
+                }
+            }
+        }
+        self.type_parameters.clear();
+        self._match(tokens, index, &TokenKind::RightCurly)?;
+        let class: Class = Class { doc: None, name: name.clone(), type_params, base_class, interfaces, methods };
+        self.classes.insert(name, class.clone());
+        Ok(Expr::new(ExprKind::Class(class), self.span_from(tokens, start, *index)))
+    }
+    fn parse_interface_def(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1;
+        let name: String = self.parse_word(tokens, index)?;
+        let mut methods: Vec<Expr> = vec![];
+        self._match(tokens, index, &TokenKind::LeftCurly)?;
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightCurly {
+            let member: usize = *index;
+            match self.parse_interface_method(tokens, index) {
+                // a trailing doc comment leaves nothing behind but its warning
+                Ok(method) if matches!(method.kind, ExprKind::Empty) => {},
+                Ok(method) => methods.push(method),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(tokens, index, member, true);
+                }
+            }
+        }
+        self._match(tokens, index, &TokenKind::RightCurly)?;
+        let interface: Interface = Interface { doc: None, name: name.clone(), methods };
+        self.interfaces.insert(name, interface.clone());
+        Ok(Expr::new(ExprKind::Interface(interface), self.span_from(tokens, start, *index)))
+    }
+    /// `function name(...) -> T;`, which is public and virtual without saying so.
+    fn parse_interface_method(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        if let TokenKind::DocComment(_) = tokens[*index].kind {
+            return self.parse_documented(tokens, index, Self::parse_interface_method);
+        }
+        let start: Span = tokens[*index].span;
+        if !matches!(&tokens[*index].kind, TokenKind::Word(word) if word == "function") {
+            return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, start, format!("expected a method signature, got {}", tokens[*index].kind))
+                .with_note("interfaces only declare methods, as in `function area() -> float;`"));
+        }
+        let function: Expr = self.parse_function(tokens, index)?;
+        let function_span: Span = function.span;
+        let function: Function = match function.kind {
+            ExprKind::Function(function) => function,
+            _ => return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function.span, "expected function"))
+        };
+        if function.class_name.is_some() || !function.type_params.is_empty() {
+            return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function_span, format!("interface method `{}` can only have a name, parameters and a return type", function.name)));
+        }
+        if !matches!(function.body.kind, ExprKind::Empty) {
+            return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function.body.span, format!("interface method `{}` cannot have a body", function.name))
+                .with_note("end the signature with `;` and define the method in the classes implementing the interface"));
+        }
+        Ok(Expr::new(ExprKind::ClassFunction(ClassFunction {
+            doc: None,
+            name: function.name,
+            args: function.args,
+            return_type: function.return_type,
+            body: function.body,
+            is_virtual: true,
+            is_override: false,
+            is_external: false,
+            access: AccessModifier::Public,
+        }), self.span_from(tokens, start, *index)))
+    }
+    /// Fields and methods may appear in any order; the word after the access
+    /// modifier tells them apart.
+    fn parse_class_member(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        if let TokenKind::DocComment(_) = tokens[*index].kind {
+            return self.parse_documented(tokens, index, Self::parse_class_member);
+        }
+        let mut lookahead: usize = *index;
+        if matches!(&tokens[lookahead].kind, TokenKind::Word(word) if word == "public" || word == "private") {
+            lookahead += 1;
+        }
+        match &tokens[lookahead].kind {
+            TokenKind::Word(word) if word == "function" || word == "virtual" || word == "override" || word == "external" => self.parse_class_function_def(tokens, index),
+            TokenKind::Word(word) if word == "constructor" => self.parse_constructor(tokens, index),
+            _ => self.parse_class_body(tokens, index),
+        }
+    }
+    /// Members are private unless marked `public`.
+    fn parse_access_modifier(&mut self, tokens: &[Token], index: &mut usize) -> AccessModifier {
+        if self._is(tokens, index, &TokenKind::Word("public".to_string())) {
+            return AccessModifier::Public;
+        }
+        self._is(tokens, index, &TokenKind::Word("private".to_string()));
+        AccessModifier::Private
+    }
+    fn parse_class_body(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        let access_modifier: AccessModifier = self.parse_access_modifier(tokens, index);
+        let mut is_named: bool = false;
+        if tokens[*index].kind == TokenKind::Word("named".to_string()) {
+            is_named = true;
+            *index += 1;
+        }
+        self._match(tokens, index, &TokenKind::Word("var".to_string()))?;
+        let name: String = self.parse_word(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Colon)?;
+        let var_type: Type = self.parse_type(tokens, index)?;
+        let mut value: Expr = Expr::new(ExprKind::Empty, tokens[*index].span);
+        if self._is(tokens, index, &TokenKind::Equal) {
+            value = self.parse_expression(tokens, index)?;
+        }
+        self._match(tokens, index, &TokenKind::Semicolon)?;
+        Ok(Expr::new(ExprKind::ClassVariable(ClassVariable {
+            doc: None,
+            name,
+            var_type,
+            initializer: Box::new(value),
+            access: access_modifier,
+            is_named
+        }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_class_function_def(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        let access_modifier: AccessModifier = self.parse_access_modifier(tokens, index);
+        let mut is_virtual: bool = false;
+        let mut is_override: bool = false;
+        let mut is_external: bool = false;
+        let modifier: Span = tokens[*index].span;
+        if tokens[*index].kind == TokenKind::Word("virtual".to_string()) {
+            is_virtual = true;
+            *index += 1;
+        } else if tokens[*index].kind == TokenKind::Word("override".to_string()) {
+            is_override = true;
+            *index += 1;
+        } else if tokens[*index].kind == TokenKind::Word("external".to_string()) {
+            is_external = true;
+            *index += 1;
+        }
+        if is_virtual && is_override {
+            return Err(Diagnostic::error(codes::CONFLICTING_MODIFIERS, modifier, "cannot be both virtual and override"));
+        } else if is_virtual && is_external {
+            return Err(Diagnostic::error(codes::CONFLICTING_MODIFIERS, modifier, "cannot be both virtual and external"));
+        } else if is_override && is_external {
+            return Err(Diagnostic::error(codes::CONFLICTING_MODIFIERS, modifier, "cannot be both override and external"));
+        }
+        let function: Expr = self.parse_function(tokens, index)?;
+        let function_span: Span = function.span;
+        let function: Function = match function.kind {
+            ExprKind::Function(function) => function,
+            _ => return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function.span, "expected function"))
+        };
+        if let Some(class_name) = &function.class_name {
+            return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function_span, format!("qualified name `{}::{}` is only allowed outside of a class", class_name, function.name)));
+        }
+        if !function.type_params.is_empty() {
+            return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function_span, format!("method `{}` cannot have type parameters", function.name))
+                .with_note("only classes and functions outside of a class can be generic"));
+        }
+        if is_external {
+            match function.body.kind {
+                ExprKind::Block(_) => return Err(Diagnostic::error(codes::EXTERNAL_WITH_BODY, function.body.span, "external function cannot have a body")),
+                ExprKind::Empty => {}
+                _ => return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, function.body.span, "expected block"))
+            }
+        }
+        Ok(Expr::new(ExprKind::ClassFunction(ClassFunction {
+            doc: None,
+            name: function.name,
+            args: function.args,
+            return_type: function.return_type,
+            body: function.body,
+            is_virtual,
+            is_override,
+            is_external,
+            access: access_modifier,
+        }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_constructor(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        let access_modifier: AccessModifier = self.parse_access_modifier(tokens, index);
+        self._match(tokens, index, &TokenKind::Word("constructor".to_string()))?;
+        let args: Vec<Parameter> = self.parse_parameters(tokens, index)?;
+        let body: Expr = self.parse_block(tokens, index)?;
+        Ok(Expr::new(ExprKind::Constructor(Constructor {
+            doc: None,
+            args,
+            body: Box::new(body),
+            access: access_modifier,
+        }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_function(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        self._match(tokens, index, &TokenKind::Word("function".to_string()))?;
+        let mut class_name: Option<String> = None;
+        let mut name: String = self.parse_word(tokens, index)?;
+        if self._is(tokens, index, &TokenKind::DoubleColon) {
+            class_name = Some(name);
+            name = self.parse_word(tokens, index)?;
+        }
+        let type_params: Vec<String> = self.parse_type_parameters(tokens, index)?;
+        // a method also sees the type parameters of its class
+        let outer: Vec<String> = self.type_parameters.clone();
+        self.type_parameters.extend(type_params.iter().cloned());
+        let declared_return_type: Option<Type> = class_name.as_ref().and_then(|class_name| self.declared_return_type(class_name, &name));
+        let result: Result<(Vec<Parameter>, Type, Expr), Diagnostic> = self.parse_function_signature_and_body(tokens, index, declared_return_type);
+        self.type_parameters = outer;
+        let (args, return_type, body): (Vec<Parameter>, Type, Expr) = result?;
+        Ok(Expr::new(ExprKind::Function(Function {
+            doc: None,
+            class_name,
+            name,
+            type_params,
+            args,
+            return_type,
+            body: Box::new(body),
+        }), self.span_from(tokens, start, *index)))
+    }
+    /// The return type of the `external` method `name` declared in `class_name`,
+    /// which its out-of-line definition may leave out.
+    fn declared_return_type(&self, class_name: &str, name: &str) -> Option<Type> {
+        self.classes.get(class_name)?.methods.iter().find_map(|member| match &member.kind {
+            ExprKind::ClassFunction(class_function) if class_function.name == name && class_function.is_external => Some(class_function.return_type.clone()),
+            _ => None,
+        })
+    }
+    /// The parameters, the return type and the body of a function, which is
+    /// `Empty` for a declaration ending in `;`. Without a `->`, the return type is
+    /// `declared_return_type` when there is one.
+    fn parse_function_signature_and_body(&mut self, tokens: &[Token], index: &mut usize, declared_return_type: Option<Type>) -> Result<(Vec<Parameter>, Type, Expr), Diagnostic> {
+        let args: Vec<Parameter> = self.parse_parameters(tokens, index)?;
+        let return_type: Type = match declared_return_type {
+            Some(return_type) if tokens[*index].kind != TokenKind::LeftArrow => return_type,
+            _ => {
+                self._match(tokens, index, &TokenKind::LeftArrow)?;
+                self.parse_type(tokens, index)?
+            },
+        };
+        if tokens[*index].kind == TokenKind::Semicolon {
+            let body: Expr = Expr::new(ExprKind::Empty, tokens[*index].span);
+            self._match(tokens, index, &TokenKind::Semicolon)?;
+            return Ok((args, return_type, body));
+        }
+        let body: Expr = self.parse_block(tokens, index)?;
+        Ok((args, return_type, body))
+    }
+    /// `<T, U>` after the name of a generic class or function, or nothing.
+    fn parse_type_parameters(&mut self, tokens: &[Token], index: &mut usize) -> Result<Vec<String>, Diagnostic> {
+        let mut type_params: Vec<String> = vec![];
+        if tokens[*index].kind != TokenKind::Less {
+            return Ok(type_params);
+        }
+        *index += 1;
+        loop {
+            type_params.push(self.parse_word(tokens, index)?);
+            if !self._is(tokens, index, &TokenKind::Comma) {
+                break;
+            }
+        }
+        self._match(tokens, index, &TokenKind::Greater)?;
+        Ok(type_params)
+    }
+    /// `<int, Box<T>>` after the name of a generic class or function.
+    fn parse_type_arguments(&mut self, tokens: &[Token], index: &mut usize) -> Result<Vec<Type>, Diagnostic> {
+        self._match(tokens, index, &TokenKind::Less)?;
+        let mut type_args: Vec<Type> = vec![];
+        loop {
+            type_args.push(self.parse_type(tokens, index)?);
+            if !self._is(tokens, index, &TokenKind::Comma) {
+                break;
+            }
+        }
+        match tokens[*index].kind {
+            TokenKind::Greater => *index += 1,
+            // `Box<Box<int>>` ends in a single `>>` token, which closes both lists
+            TokenKind::ShiftRight if self.pending_greater => {
+                self.pending_greater = false;
+                *index += 1;
+            },
+            TokenKind::ShiftRight => self.pending_greater = true,
+            ref kind => return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected `>`, got {}", kind))),
+        }
+        Ok(type_args)
+    }
+    /// Whether the `<` after the name at `index` starts the type arguments of a
+    /// call, `max<int>(a, b)`, rather than a comparison. Like in C#, it does when
+    /// a list of types closed by `>` and followed by `(` can be parsed.
+    fn is_generic_call(&mut self, tokens: &[Token], index: usize) -> bool {
+        let mut lookahead: usize = index + 1;
+        let diagnostics: usize = self.diagnostics.len();
+        let parsed: bool = self.parse_type_arguments(tokens, &mut lookahead).is_ok() && !self.pending_greater;
+        self.pending_greater = false;
+        self.diagnostics.truncate(diagnostics);
+        parsed && tokens[lookahead].kind == TokenKind::LeftParen
+    }
+    /// A parenthesized list of `name: type` parameters, each optionally `anonymous`
+    /// and `mutable`, in that order.
+    fn parse_parameters(&mut self, tokens: &[Token], index: &mut usize) -> Result<Vec<Parameter>, Diagnostic> {
+        self._match(tokens, index, &TokenKind::LeftParen)?;
+        let mut args: Vec<Parameter> = vec![];
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightParen {
+            let is_anonymous: bool = self._is(tokens, index, &TokenKind::Word("anonymous".to_string()));
+            let is_mutable: bool = self._is(tokens, index, &TokenKind::Word("mutable".to_string()));
+            let arg_name: String = self.parse_word(tokens, index)?;
+            self._match(tokens, index, &TokenKind::Colon)?;
+            let arg_type: Type = self.parse_type(tokens, index)?;
+            args.push(Parameter { name: arg_name, param_type: arg_type, is_anonymous, is_mutable });
+            if self._is(tokens, index, &TokenKind::Comma) {
+                continue;
+            }
+            if tokens[*index].kind != TokenKind::RightParen {
+                return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected `,` or `)`, got {}", tokens[*index].kind)));
+            }
+        }
+        self._match(tokens, index, &TokenKind::RightParen)?;
+        Ok(args)
+    }
+    fn parse_block(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        self._match(tokens, index, &TokenKind::LeftCurly)?;
+        let mut exprs: Vec<Expr> = vec![];
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightCurly {
+            let statement: usize = *index;
+            match self.parse_token(tokens, index) {
+                Ok(expr) => exprs.push(expr),
+                Err(diagnostic) => {
+                    self.diagnostics.push(diagnostic);
+                    self.synchronize(tokens, index, statement, true);
+                }
+            }
+        }
+        self._match(tokens, index, &TokenKind::RightCurly)?;
+        Ok(Expr::new(ExprKind::Block(Block { exprs }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_if(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip if
+        let condition: Expr = self.parse_expression(tokens, index)?;
+        let then_branch: Expr = self.parse_block(tokens, index)?;
+        let mut else_branch: Option<Box<Expr>> = None;
+        if self._is(tokens, index, &TokenKind::Word("else".to_string())) {
+            if tokens[*index].kind == TokenKind::Word("if".to_string()) {
+                else_branch = Some(Box::new(self.parse_if(tokens, index)?));
+            } else {
+                else_branch = Some(Box::new(self.parse_block(tokens, index)?));
+            }
+        }
+        Ok(Expr::new(ExprKind::If(If {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch,
+        }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_while(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip while
+        let condition: Expr = self.parse_expression(tokens, index)?;
+        let body: Expr = self.parse_block(tokens, index)?;
+        Ok(Expr::new(ExprKind::While(While { condition: Box::new(condition), body: Box::new(body) }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_for(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip for
+        if !self._is(tokens, index, &TokenKind::LeftParen) {
+            let variable: String = self.parse_word(tokens, index)?;
+            self._match(tokens, index, &TokenKind::Word("in".to_string()))?;
+            let range_start: Expr = self.parse_expression(tokens, index)?;
+            self._match(tokens, index, &TokenKind::DotDot)?;
+            let range_end: Expr = self.parse_expression(tokens, index)?;
+            let body: Expr = self.parse_block(tokens, index)?;
+            return Ok(Expr::new(ExprKind::ForRange(ForRange {
+                variable,
+                start: Box::new(range_start),
+                end: Box::new(range_end),
+                body: Box::new(body),
+            }), self.span_from(tokens, start, *index)));
+        }
+        let initializer: Expr = match &tokens[*index].kind {
+            TokenKind::Word(word) if word == "var" || word == "const" => self.parse_variable(tokens, index, word == "const")?,
+            TokenKind::Semicolon => {
+                let span: Span = tokens[*index].span;
+                *index += 1;
+                Expr::new(ExprKind::Empty, span)
+            },
+            _ => self.parse_expression_statement(tokens, index)?,
+        };
+        let mut condition: Expr = Expr::new(ExprKind::Empty, tokens[*index].span);
+        if tokens[*index].kind != TokenKind::Semicolon {
+            condition = self.parse_expression(tokens, index)?;
+        }
+        self._match(tokens, index, &TokenKind::Semicolon)?;
+        let mut step: Expr = Expr::new(ExprKind::Empty, tokens[*index].span);
+        if tokens[*index].kind != TokenKind::RightParen {
+            step = self.parse_expression(tokens, index)?;
+        }
+        self._match(tokens, index, &TokenKind::RightParen)?;
+        let body: Expr = self.parse_block(tokens, index)?;
+        Ok(Expr::new(ExprKind::For(For {
+            initializer: Box::new(initializer),
+            condition: Box::new(condition),
+            step: Box::new(step),
+            body: Box::new(body),
+        }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_function_call(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        let name: String = self.parse_word(tokens, index)?;
+        let type_args: Vec<Type> = if tokens[*index].kind == TokenKind::Less { self.parse_type_arguments(tokens, index)? } else { vec![] };
+        let args: Vec<Expr> = self.parse_arguments(tokens, index)?;
+        let span: Span = self.span_from(tokens, start, *index);
+        if self.internal_functions.contains(&name) {
+            Ok(Expr::new(ExprKind::InternalFunctionCall(FunctionCall { name, type_args, args }), span))
+        } else {
+            Ok(Expr::new(ExprKind::FunctionCall(FunctionCall { name, type_args, args }), span))
+        }
+    }
+    /// A parenthesized, comma separated list of call arguments, each optionally
+    /// preceded by the name of its parameter: `(1, label: 2)`.
+    fn parse_arguments(&mut self, tokens: &[Token], index: &mut usize) -> Result<Vec<Expr>, Diagnostic> {
+        self._match(tokens, index, &TokenKind::LeftParen)?;
+        let mut args: Vec<Expr> = vec![];
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightParen {
+            if let (TokenKind::Word(label), TokenKind::Colon) = (&tokens[*index].kind, &tokens[*index + 1].kind) {
+                let start: Span = tokens[*index].span;
+                let label: String = label.clone();
+                *index += 2;
+                let value: Expr = self.parse_expression(tokens, index)?;
+                let span: Span = start.to(&value.span);
+                args.push(Expr::new(ExprKind::NamedArgument(label, Box::new(value)), span));
+            } else {
+                args.push(self.parse_expression(tokens, index)?);
+            }
+            if self._is(tokens, index, &TokenKind::Comma) {
+                continue;
+            }
+            if tokens[*index].kind != TokenKind::RightParen {
+                return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected `,` or `)`, got {}", tokens[*index].kind)));
+            }
+        }
+        self._match(tokens, index, &TokenKind::RightParen)?;
+        Ok(args)
+    }
+    fn parse_return(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1;
+        let mut value: Expr = Expr::new(ExprKind::Empty, tokens[*index].span);
+        if tokens[*index].kind != TokenKind::Semicolon {
+            value = self.parse_expression(tokens, index)?;
+        }
+        self._match(tokens, index, &TokenKind::Semicolon)?;
+        Ok(Expr::new(ExprKind::Return(Box::new(value)), self.span_from(tokens, start, *index)))
+    }
+    fn parse_variable(&mut self, tokens: &[Token], index: &mut usize, constant: bool) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip to name
+        let name: String = self.parse_word(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Colon)?;
+        let var_type: Type = self.parse_type(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Equal)?;
+        let value: Expr = self.parse_expression(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Semicolon)?;
+        let variable_declaration: VariableDeclaration = VariableDeclaration { name, value: Box::new(value), var_type, constant };
+        Ok(Expr::new(ExprKind::VariableDeclaration(variable_declaration), self.span_from(tokens, start, *index)))
+    }
+    fn parse_new(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip new
+        let class_name: String = self.parse_word(tokens, index)?;
+        let type_args: Vec<Type> = if tokens[*index].kind == TokenKind::Less { self.parse_type_arguments(tokens, index)? } else { vec![] };
+        let args: Vec<Expr> = self.parse_arguments(tokens, index)?;
+        Ok(Expr::new(ExprKind::New(New { class_name, type_args, args }), self.span_from(tokens, start, *index)))
+    }
+    fn parse_downcast(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        *index += 1; // skip downcast
+        self._match(tokens, index, &TokenKind::Less)?;
+        let class_name: String = self.parse_word(tokens, index)?;
+        self._match(tokens, index, &TokenKind::Greater)?;
+        self._match(tokens, index, &TokenKind::LeftParen)?;
+        let value: Expr = self.parse_expression(tokens, index)?;
+        self._match(tokens, index, &TokenKind::RightParen)?;
+        Ok(Expr::new(ExprKind::Downcast(class_name, Box::new(value)), self.span_from(tokens, start, *index)))
+    }
+    fn parse_type(&mut self, tokens: &[Token], index: &mut usize) -> Result<Type, Diagnostic> {
+        let mut type_: Type = self.parse_type_name(tokens, index)?;
+        while tokens[*index].kind == TokenKind::Star {
+            type_ = Type::Pointer(Box::new(type_));
+            self._match(tokens, index, &TokenKind::Star)?;
+        }
+        Ok(type_)
+    }
+    /// The type after `as`, where a `*` followed by an operand is a multiplication:
+    /// `x as int * 2` but `p as Animal*`.
+    fn parse_cast_type(&mut self, tokens: &[Token], index: &mut usize) -> Result<Type, Diagnostic> {
+        let mut type_: Type = self.parse_type_name(tokens, index)?;
+        while tokens[*index].kind == TokenKind::Star && !matches!(tokens[*index + 1].kind, TokenKind::Word(_) | TokenKind::Int(..) | TokenKind::Float(..)
+            | TokenKind::Char(_) | TokenKind::String(_) | TokenKind::LeftParen | TokenKind::Minus | TokenKind::Bang | TokenKind::Tilde) {
+            type_ = Type::Pointer(Box::new(type_));
+            self._match(tokens, index, &TokenKind::Star)?;
+        }
+        Ok(type_)
+    }
+    fn parse_type_name(&mut self, tokens: &[Token], index: &mut usize) -> Result<Type, Diagnostic> {
+        let type_: Type = match tokens[*index].kind.clone() {
+            TokenKind::Word(word) if self.type_parameters.contains(&word) => Type::Parameter(word),
+            TokenKind::Word(word) => match Type::from_name(&word) {
+                Some(type_) => type_,
+                None => {
+                    *index += 1;
+                    let type_args: Vec<Type> = if tokens[*index].kind == TokenKind::Less { self.parse_type_arguments(tokens, index)? } else { vec![] };
+                    return Ok(Type::Class(word, type_args));
+                },
+            },
+            // `[T; N]` or `[T]`
+            TokenKind::LeftBracket => {
+                *index += 1;
+                let element_type: Type = self.parse_type(tokens, index)?;
+                let type_: Type = if tokens[*index].kind == TokenKind::Semicolon {
+                    *index += 1;
+                    Type::Array(Box::new(element_type), self.parse_array_length(tokens, index)?)
+                } else {
+                    Type::Slice(Box::new(element_type))
+                };
+                self._match(tokens, index, &TokenKind::RightBracket)?;
+                return Ok(type_);
+            },
+            kind => return Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected type, got {}", kind)))
+        };
+        *index += 1;
+        Ok(type_)
+    }
+    /// Precedence climbing over `BINARY_OPERATORS`: only operators binding at
+    /// least as tightly as `min_precedence` are consumed at this level.
+    fn parse_binary(&mut self, tokens: &[Token], index: &mut usize, min_precedence: u8) -> Result<Expr, Diagnostic> {
+        let mut expr: Expr = self.parse_cast(tokens, index)?;
+        while let Some(precedence) = binary_precedence(&tokens[*index].kind) {
+            if precedence < min_precedence {
+                break;
+            }
+            let op: TokenKind = tokens[*index].kind.clone();
+            *index += 1;
+            // every operator is left associative, so the right side only takes tighter operators
+            let right: Expr = self.parse_binary(tokens, index, precedence + 1)?;
+            let span: Span = expr.span.to(&right.span);
+            expr = Expr::new(ExprKind::BinaryOp(Box::new(expr), op, Box::new(right)), span);
+        }
+        Ok(expr)
+    }
+    /// `as` binds tighter than every binary operator but looser than the unary ones,
+    /// so `-x as u8 * 2` means `((-x) as u8) * 2`.
+    fn parse_cast(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let mut expr: Expr = self.parse_unary(tokens, index)?;
+        while tokens[*index].kind == TokenKind::Word("as".to_string()) {
+            *index += 1;
+            let target: Type = self.parse_cast_type(tokens, index)?;
+            let span: Span = expr.span.to(&tokens[*index - 1].span);
+            expr = Expr::new(ExprKind::Cast(Box::new(expr), target), span);
+        }
+        Ok(expr)
+    }
+    fn parse_unary(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        match tokens[*index].kind {
+            TokenKind::Bang | TokenKind::Minus | TokenKind::Tilde => {
+                let start: Span = tokens[*index].span;
+                let op: TokenKind = tokens[*index].kind.clone();
+                *index += 1;
+                let operand: Expr = self.parse_unary(tokens, index)?;
+                let span: Span = start.to(&operand.span);
+                Ok(Expr::new(ExprKind::UnaryOp(op, Box::new(operand)), span))
+            },
+            _ => self.parse_member(tokens, index),
+        }
+    }
+    /// Field accesses (`.field`, `->field`), method calls (`.method()`, `->method()`) and
+    /// indexing (`[index]`), in any order.
+    fn parse_member(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let mut expr: Expr = self.parse_primary(tokens, index)?;
+        loop {
+            match tokens[*index].kind {
+                TokenKind::Dot => {
+                    *index += 1;
+                    let name: String = self.parse_word(tokens, index)?;
+                    if tokens[*index].kind != TokenKind::LeftParen {
+                        let span: Span = expr.span.to(&tokens[*index - 1].span);
+                        expr = Expr::new(ExprKind::Member(Box::new(expr), name), span);
+                        continue;
+                    }
+                    let args: Vec<Expr> = self.parse_arguments(tokens, index)?;
+                    let span: Span = expr.span.to(&tokens[*index - 1].span);
+                    expr = Expr::new(ExprKind::MemberFunctionCall(Box::new(expr), FunctionCall { name, type_args: vec![], args }), span);
+                },
+                TokenKind::LeftArrow => {
+                    *index += 1;
+                    let name: String = self.parse_word(tokens, index)?;
+                    if tokens[*index].kind != TokenKind::LeftParen {
+                        let span: Span = expr.span.to(&tokens[*index - 1].span);
+                        expr = Expr::new(ExprKind::PointerMember(Box::new(expr), name), span);
+                        continue;
+                    }
+                    let args: Vec<Expr> = self.parse_arguments(tokens, index)?;
+                    let span: Span = expr.span.to(&tokens[*index - 1].span);
+                    expr = Expr::new(ExprKind::PointerMemberFunctionCall(Box::new(expr), FunctionCall { name, type_args: vec![], args }), span);
+                },
+                TokenKind::LeftBracket => {
+                    *index += 1;
+                    let position: Expr = self.parse_expression(tokens, index)?;
+                    self._match(tokens, index, &TokenKind::RightBracket)?;
+                    let span: Span = expr.span.to(&tokens[*index - 1].span);
+                    expr = Expr::new(ExprKind::Index(Box::new(expr), Box::new(position)), span);
+                },
+                _ => return Ok(expr),
+            }
+        }
+    }
+    fn parse_primary(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let span: Span = tokens[*index].span;
+        match tokens[*index].kind.clone() {
+            TokenKind::Word(word) => {
+                if word == "new" {
+                    self.parse_new(tokens, index)
+                } else if word == "downcast" && tokens[*index + 1].kind == TokenKind::Less {
+                    self.parse_downcast(tokens, index)
+                } else if word == "true" || word == "false" {
+                    *index += 1;
+                    Ok(Expr::new(ExprKind::Bool(word == "true"), span))
+                } else if word == "null" {
+                    *index += 1;
+                    Ok(Expr::new(ExprKind::Null, span))
+                } else if tokens[*index + 1].kind == TokenKind::LeftParen || (tokens[*index + 1].kind == TokenKind::Less && self.is_generic_call(tokens, *index)) {
+                    self.parse_function_call(tokens, index)
+                } else {
+                    self.parse_var(tokens, index)
+                }
+            },
+            TokenKind::Int(number, suffix) => {
+                *index += 1;
+                Ok(Expr::new(ExprKind::Int(number, suffix), span))
+            },
+            TokenKind::Float(number, suffix) => {
+                *index += 1;
+                Ok(Expr::new(ExprKind::Float(number, suffix), span))
+            },
+            TokenKind::Char(c) => {
+                *index += 1;
+                Ok(Expr::new(ExprKind::Char(c), span))
+            },
+            TokenKind::String(string) => {
+                *index += 1;
+                Ok(Expr::new(ExprKind::String(string), span))
+            },
+            TokenKind::LeftParen => {
+                *index += 1;
+                let expr: Expr = self.parse_expression(tokens, index)?;
+                self._match(tokens, index, &TokenKind::RightParen)?;
+                Ok(expr)
+            },
+            TokenKind::LeftBracket => self.parse_array(tokens, index),
+            kind => Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, span, format!("expected expression, got {}", kind)))
+        }
+    }
+    /// `[a, b, c]`, or `[value; N]` for `N` copies of `value`.
+    fn parse_array(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let start: Span = tokens[*index].span;
+        self._match(tokens, index, &TokenKind::LeftBracket)?;
+        let mut elements: Vec<Expr> = vec![];
+        while tokens[*index].kind != TokenKind::Eof && tokens[*index].kind != TokenKind::RightBracket {
+            elements.push(self.parse_expression(tokens, index)?);
+            if elements.len() == 1 && tokens[*index].kind == TokenKind::Semicolon {
+                *index += 1;
+                let length: u64 = self.parse_array_length(tokens, index)?;
+                self._match(tokens, index, &TokenKind::RightBracket)?;
+                let value: Expr = elements.remove(0);
+                return Ok(Expr::new(ExprKind::ArrayRepeat(Box::new(value), length), self.span_from(tokens, start, *index)));
+            }
+            if tokens[*index].kind == TokenKind::Comma {
+                self._match(tokens, index, &TokenKind::Comma)?;
+            }
+        }
+        self._match(tokens, index, &TokenKind::RightBracket)?;
+        Ok(Expr::new(ExprKind::Array(elements), self.span_from(tokens, start, *index)))
+    }
+    fn parse_array_length(&mut self, tokens: &[Token], index: &mut usize) -> Result<u64, Diagnostic> {
+        match tokens[*index].kind {
+            TokenKind::Int(length, None) => {
+                *index += 1;
+                Ok(length)
+            },
+            ref kind => Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected array length, got {}", kind))),
+        }
+    }
+    fn parse_var(&mut self, tokens: &[Token], index: &mut usize) -> Result<Expr, Diagnostic> {
+        let span: Span = tokens[*index].span;
+        let name: String = self.parse_word(tokens, index)?;
+        Ok(Expr::new(ExprKind::Variable(name), span))
+    }
+    fn parse_word(&mut self, tokens: &[Token], index: &mut usize) -> Result<String, Diagnostic> {
+        match tokens[*index].kind.clone() {
+            TokenKind::Word(word) => {
+                *index += 1;
+                Ok(word)
+            },
+            kind => Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, tokens[*index].span, format!("expected identifier, got {}", kind)))
+        }
+    }
+    fn span_from(&self, tokens: &[Token], start: Span, index: usize) -> Span {
+        start.to(&tokens[index.saturating_sub(1)].span)
+    }
+    fn _match(&mut self, tokens: &[Token], index: &mut usize, token: &TokenKind) -> Result<TokenKind, Diagnostic> {
+        if tokens[*index].kind == *token {
+            *index += 1;
+            return Ok(token.clone());
+        }
+        let mut span: Span = tokens[*index].span;
+        if *token == TokenKind::Semicolon && *index > 0 {
+            // a missing `;` belongs at the end of the previous line, not on the next statement
+            let previous: Span = tokens[*index - 1].span;
+            span = Span { file: previous.file, line: previous.end_line, column: previous.end_column, end_line: previous.end_line, end_column: previous.end_column + 1 };
+        }
+        Err(Diagnostic::error(codes::UNEXPECTED_TOKEN, span, format!("expected {}, got {}", token, tokens[*index].kind)))
+    }
+    fn _is(&mut self, tokens: &[Token], index: &mut usize, token: &TokenKind) -> bool {
+        if tokens[*index].kind == *token {
+            *index += 1;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use crate::ast::{AccessModifier, Class, ClassFunction, Expr, ExprKind, Function, Parameter, Type, VariableDeclaration};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::Gemstone;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScopeKind {
+    Module,
+    Class,
+    Function,
+    Block,
+    Loop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Variable { constant: bool },
+    Parameter { mutable: bool },
+    Field,
+    Method,
+    Function,
+    Class,
+    Interface,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub symbol_type: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub symbols: HashMap<String, Symbol>,
+}
+
+/// A stack of lexical scopes, innermost last.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable { scopes: vec![] }
+    }
+    pub fn push(&mut self, kind: ScopeKind) {
+        self.scopes.push(Scope { kind, symbols: HashMap::new() });
+    }
+    pub fn pop(&mut self) {
+        self.scopes.pop();
+    }
+    /// Declares `symbol` in the innermost scope, returning the existing symbol
+    /// if the name is already taken there.
+    pub fn declare(&mut self, symbol: Symbol) -> Result<(), Symbol> {
+        let scope: &mut Scope = self.scopes.last_mut().expect("no scope to declare in");
+        if let Some(existing) = scope.symbols.get(&symbol.name) {
+            return Err(existing.clone());
+        }
+        scope.symbols.insert(symbol.name.clone(), symbol);
+        Ok(())
+    }
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes.iter().rev().find_map(|scope| scope.symbols.get(name))
+    }
+    /// Whether a `break` or `continue` here would leave a loop of the current function.
+    pub fn in_loop(&self) -> bool {
+        for scope in self.scopes.iter().rev() {
+            match scope.kind {
+                ScopeKind::Loop => return true,
+                ScopeKind::Function | ScopeKind::Class | ScopeKind::Module => return false,
+                ScopeKind::Block => {},
+            }
+        }
+        false
+    }
+    /// Looks `name` up in every scope except the innermost one.
+    pub fn lookup_outer(&self, name: &str) -> Option<&Symbol> {
+        let outer: &[Scope] = &self.scopes[..self.scopes.len().saturating_sub(1)];
+        outer.iter().rev().find_map(|scope| scope.symbols.get(name))
+    }
+}
+
+impl Gemstone {
+    /// Builds the scopes of the program, binding every variable use to its
+    /// declaration. Undefined names and duplicate declarations are errors,
+    /// shadowing a variable from an enclosing scope is a warning.
+    pub fn resolve(&mut self, exprs: &[Expr]) -> Result<(), Vec<Diagnostic>> {
+        self.bindings.clear();
+        self.symbols = SymbolTable::new();
+        self.symbols.push(ScopeKind::Module);
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        for expr in exprs {
+            let symbol: Symbol = match &expr.kind {
+                ExprKind::Class(class) => Symbol { name: class.name.clone(), kind: SymbolKind::Class, symbol_type: Type::Class(class.name.clone(), vec![]), span: expr.span },
+                ExprKind::Interface(interface) => Symbol { name: interface.name.clone(), kind: SymbolKind::Interface, symbol_type: Type::Class(interface.name.clone(), vec![]), span: expr.span },
+                // out-of-line method definitions belong to their class, not the module
+                ExprKind::Function(function) if function.class_name.is_none() => Symbol { name: function.name.clone(), kind: SymbolKind::Function, symbol_type: function.return_type.clone(), span: expr.span },
+                _ => continue,
+            };
+            if let Err(diagnostic) = self.resolve_declare(symbol) {
+                diagnostics.push(diagnostic);
+            }
+        }
+        for expr in exprs {
+            self.resolve_expr(expr, &mut diagnostics);
+        }
+        self.symbols.pop();
+
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        // hand the warnings back with the errors so they are reported in source order
+        diagnostics.append(&mut self.warnings);
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.span.file, diagnostic.span.line, diagnostic.span.column));
+        Err(diagnostics)
+    }
+    fn resolve_declare(&mut self, symbol: Symbol) -> Result<(), Diagnostic> {
+        let name: String = symbol.name.clone();
+        let span: Span = symbol.span;
+        let shadowing: bool = matches!(symbol.kind, SymbolKind::Variable { .. });
+        if let Err(existing) = self.symbols.declare(symbol) {
+            return Err(Diagnostic::error(codes::DUPLICATE_DECLARATION, span, format!("`{}` is already declared in this scope", name))
+                .with_note(format!("previous declaration at {}:{}", existing.span.line, existing.span.column)));
+        }
+        if shadowing {
+            if let Some(outer) = self.symbols.lookup_outer(&name) {
+                if matches!(outer.kind, SymbolKind::Variable { .. } | SymbolKind::Parameter { .. } | SymbolKind::Field) {
+                    let outer_span: Span = outer.span;
+                    self.warnings.push(Diagnostic::warning(codes::SHADOWED_NAME, span, format!("`{}` shadows a declaration from an enclosing scope", name))
+                        .with_note(format!("shadowed declaration at {}:{}", outer_span.line, outer_span.column)));
+                }
+            }
+        }
+        Ok(())
+    }
+    fn resolve_expr(&mut self, expr: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+        match &expr.kind {
+            ExprKind::Class(class) => self.resolve_class(class, diagnostics),
+            ExprKind::Function(function) => self.resolve_function(expr.span, function, diagnostics),
+            ExprKind::Block(block) => {
+                self.symbols.push(ScopeKind::Block);
+                for expr in &block.exprs {
+                    self.resolve_expr(expr, diagnostics);
+                }
+                self.symbols.pop();
+            },
+            ExprKind::VariableDeclaration(variable_declaration) => self.resolve_variable_declaration(expr.span, variable_declaration, diagnostics),
+            ExprKind::Variable(name) => match self.symbols.lookup(name) {
+                Some(symbol) if matches!(symbol.kind, SymbolKind::Variable { .. } | SymbolKind::Parameter { .. } | SymbolKind::Field) => {
+                    self.bindings.insert(expr.span, symbol.clone());
+                },
+                Some(symbol) => diagnostics.push(Diagnostic::error(codes::UNDEFINED_NAME, expr.span, format!("expected a value, found {} `{}`", match symbol.kind {
+                    SymbolKind::Class => "class",
+                    SymbolKind::Interface => "interface",
+                    SymbolKind::Method => "method",
+                    _ => "function",
+                }, name))),
+                None => diagnostics.push(Diagnostic::error(codes::UNDEFINED_NAME, expr.span, format!("cannot find value `{}` in this scope", name))),
+            },
+            ExprKind::FunctionCall(function_call) => {
+                match self.symbols.lookup(&function_call.name) {
+                    Some(symbol) if matches!(symbol.kind, SymbolKind::Function) => {},
+                    // inside a class, a method of the class is called by its bare name
+                    Some(symbol) if matches!(symbol.kind, SymbolKind::Method) => {
+                        self.bindings.insert(expr.span, symbol.clone());
+                    },
+                    _ => diagnostics.push(Diagnostic::error(codes::UNDEFINED_NAME, expr.span, format!("cannot find function `{}` in this scope", function_call.name))),
+                }
+                for arg in &function_call.args {
+                    self.resolve_expr(arg, diagnostics);
+                }
+            },
+            ExprKind::InternalFunctionCall(function_call) => {
+                for arg in &function_call.args {
+                    self.resolve_expr(arg, diagnostics);
+                }
+            },
+            ExprKind::MemberFunctionCall(parent, function_call) | ExprKind::PointerMemberFunctionCall(parent, function_call) => {
+                self.resolve_expr(parent, diagnostics);
+                for arg in &function_call.args {
+                    self.resolve_expr(arg, diagnostics);
+                }
+            },
+            ExprKind::New(new) => {
+                for arg in &new.args {
+                    self.resolve_expr(arg, diagnostics);
+                }
+            },
+            ExprKind::Member(parent, _) | ExprKind::PointerMember(parent, _) => self.resolve_expr(parent, diagnostics),
+            ExprKind::NamedArgument(_, value) => self.resolve_expr(value, diagnostics),
+            ExprKind::Assignment(target, _, value) => {
+                self.resolve_expr(target, diagnostics);
+                self.resolve_expr(value, diagnostics);
+            },
+            ExprKind::Return(value) => self.resolve_expr(value, diagnostics),
+            ExprKind::UnaryOp(_, operand) | ExprKind::Cast(operand, _) | ExprKind::Downcast(_, operand) | ExprKind::ArrayRepeat(operand, _) => self.resolve_expr(operand, diagnostics),
+            ExprKind::Array(elements) => {
+                for element in elements {
+                    self.resolve_expr(element, diagnostics);
+                }
+            },
+            ExprKind::Index(sequence, position) => {
+                self.resolve_expr(sequence, diagnostics);
+                self.resolve_expr(position, diagnostics);
+            },
+            ExprKind::BinaryOp(left, _, right) => {
+                self.resolve_expr(left, diagnostics);
+                self.resolve_expr(right, diagnostics);
+            },
+            ExprKind::If(if_expr) => {
+                self.resolve_expr(&if_expr.condition, diagnostics);
+                self.resolve_expr(&if_expr.then_branch, diagnostics);
+                if let Some(else_branch) = &if_expr.else_branch {
+                    self.resolve_expr(else_branch, diagnostics);
+                }
+            },
+            ExprKind::While(while_expr) => {
+                self.resolve_expr(&while_expr.condition, diagnostics);
+                self.symbols.push(ScopeKind::Loop);
+                self.resolve_block_contents(&while_expr.body, diagnostics);
+                self.symbols.pop();
+            },
+            // the loop header and the body share a scope, so the body cannot redeclare the loop variable
+            ExprKind::For(for_expr) => {
+                self.symbols.push(ScopeKind::Loop);
+                self.resolve_expr(&for_expr.initializer, diagnostics);
+                self.resolve_expr(&for_expr.condition, diagnostics);
+                self.resolve_expr(&for_expr.step, diagnostics);
+                self.resolve_block_contents(&for_expr.body, diagnostics);
+                self.symbols.pop();
+            },
+            ExprKind::ForRange(for_range) => {
+                self.resolve_expr(&for_range.start, diagnostics);
+                self.resolve_expr(&for_range.end, diagnostics);
+                self.symbols.push(ScopeKind::Loop);
+                let symbol: Symbol = Symbol { name: for_range.variable.clone(), kind: SymbolKind::Variable { constant: true }, symbol_type: Type::Int, span: expr.span };
+                if let Err(diagnostic) = self.resolve_declare(symbol) {
+                    diagnostics.push(diagnostic);
+                }
+                self.resolve_block_contents(&for_range.body, diagnostics);
+                self.symbols.pop();
+            },
+            ExprKind::Break | ExprKind::Continue => {
+                if !self.symbols.in_loop() {
+                    let keyword: &str = if matches!(expr.kind, ExprKind::Break) { "break" } else { "continue" };
+                    diagnostics.push(Diagnostic::error(codes::OUTSIDE_LOOP, expr.span, format!("`{}` outside of a loop", keyword)));
+                }
+            },
+            // interface methods have no bodies to resolve
+            ExprKind::Interface(_) | ExprKind::ClassFunction(_) | ExprKind::ClassVariable(_) | ExprKind::Constructor(_) | ExprKind::Int(..) | ExprKind::Float(..) | ExprKind::Bool(_) | ExprKind::Char(_)
+                | ExprKind::String(_) | ExprKind::Null | ExprKind::Empty => {},
+        }
+    }
+    fn resolve_class(&mut self, class: &Class, diagnostics: &mut Vec<Diagnostic>) {
+        let depth: usize = self.resolve_enter_class(class, diagnostics);
+        for member in &class.methods {
+            match &member.kind {
+                ExprKind::ClassVariable(class_variable) => self.resolve_expr(&class_variable.initializer, diagnostics),
+                ExprKind::ClassFunction(class_function) => self.resolve_class_function(member.span, class_function, diagnostics),
+                ExprKind::Constructor(constructor) => self.resolve_function_body(member.span, &constructor.args, &constructor.body, diagnostics),
+                _ => {},
+            }
+        }
+        self.resolve_leave_class(depth);
+    }
+    /// Pushes the scopes of `class` and its ancestors, returning how many were pushed.
+    fn resolve_enter_class(&mut self, class: &Class, diagnostics: &mut Vec<Diagnostic>) -> usize {
+        // inherited members live in their own scope so redeclaring one shadows it instead of clashing
+        let mut ancestors: Vec<Class> = vec![];
+        let mut base_class: Option<String> = class.base_class.clone();
+        while let Some(name) = base_class {
+            match self.classes.get(&name) {
+                Some(base) if ancestors.iter().all(|ancestor| ancestor.name != name) => {
+                    base_class = base.base_class.clone();
+                    ancestors.push(base.clone());
+                },
+                _ => break,
+            }
+        }
+        for ancestor in ancestors.iter().rev() {
+            self.symbols.push(ScopeKind::Class);
+            self.resolve_class_members(ancestor, true, &mut vec![]);
+        }
+        self.symbols.push(ScopeKind::Class);
+        self.resolve_class_members(class, false, diagnostics);
+        ancestors.len() + 1
+    }
+    fn resolve_leave_class(&mut self, depth: usize) {
+        for _ in 0..depth {
+            self.symbols.pop();
+        }
+    }
+    fn resolve_class_members(&mut self, class: &Class, inherited: bool, diagnostics: &mut Vec<Diagnostic>) {
+        for member in &class.methods {
+            let symbol: Symbol = match &member.kind {
+                ExprKind::ClassVariable(class_variable) if inherited && class_variable.access == AccessModifier::Private => continue,
+                ExprKind::ClassFunction(class_function) if inherited && class_function.access == AccessModifier::Private => continue,
+                ExprKind::ClassVariable(class_variable) => Symbol { name: class_variable.name.clone(), kind: SymbolKind::Field, symbol_type: class_variable.var_type.clone(), span: member.span },
+                ExprKind::ClassFunction(class_function) => Symbol { name: class_function.name.clone(), kind: SymbolKind::Method, symbol_type: class_function.return_type.clone(), span: member.span },
+                _ => continue,
+            };
+            if let Err(diagnostic) = self.resolve_declare(symbol) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    fn resolve_class_function(&mut self, span: Span, class_function: &ClassFunction, diagnostics: &mut Vec<Diagnostic>) {
+        self.resolve_function_body(span, &class_function.args, &class_function.body, diagnostics);
+    }
+    fn resolve_function(&mut self, span: Span, function: &Function, diagnostics: &mut Vec<Diagnostic>) {
+        // an out-of-line method sees the members of its class; an unknown class is reported by the type checker
+        let class: Option<Class> = function.class_name.as_ref().and_then(|class_name| self.classes.get(class_name).cloned());
+        let depth: usize = match &class {
+            Some(class) => self.resolve_enter_class(class, &mut vec![]),
+            None => 0,
+        };
+        self.resolve_function_body(span, &function.args, &function.body, diagnostics);
+        self.resolve_leave_class(depth);
+    }
+    /// Parameters have no spans of their own, so they are attributed to the function declaring them.
+    fn resolve_function_body(&mut self, span: Span, args: &[Parameter], body: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+        self.symbols.push(ScopeKind::Function);
+        for arg in args {
+            let symbol: Symbol = Symbol { name: arg.name.clone(), kind: SymbolKind::Parameter { mutable: arg.is_mutable }, symbol_type: arg.param_type.clone(), span };
+            if let Err(diagnostic) = self.resolve_declare(symbol) {
+                diagnostics.push(diagnostic);
+            }
+        }
+        // the outermost block shares the parameters' scope, as it does in C++
+        self.resolve_block_contents(body, diagnostics);
+        self.symbols.pop();
+    }
+    /// Resolves the statements of a block in the current scope instead of a new one.
+    fn resolve_block_contents(&mut self, body: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+        match &body.kind {
+            ExprKind::Block(block) => {
+                for expr in &block.exprs {
+                    self.resolve_expr(expr, diagnostics);
+                }
+            },
+            _ => self.resolve_expr(body, diagnostics),
+        }
+    }
+    fn resolve_variable_declaration(&mut self, span: Span, variable_declaration: &VariableDeclaration, diagnostics: &mut Vec<Diagnostic>) {
+        // the initializer is resolved first: `var x: int = x;` refers to an outer `x`
+        self.resolve_expr(&variable_declaration.value, diagnostics);
+        let symbol: Symbol = Symbol {
+            name: variable_declaration.name.clone(),
+            kind: SymbolKind::Variable { constant: variable_declaration.constant },
+            symbol_type: variable_declaration.var_type.clone(),
+            span,
+        };
+        if let Err(diagnostic) = self.resolve_declare(symbol) {
+            diagnostics.push(diagnostic);
+        }
+    }
+}
@@ -0,0 +1,1479 @@
+use std::collections::HashMap;
+
+use crate::ast::{AccessModifier, Class, ClassFunction, ClassVariable, Constructor, Expr, ExprKind, Function, FunctionCall, Interface, New, Parameter, Type, VariableDeclaration};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use crate::lexer::TokenKind;
+use crate::resolve::SymbolKind;
+use crate::Gemstone;
+
+/// Something the body of a generic class or function does with a value of one of
+/// its type parameters, which every type argument for it must support.
+#[derive(Debug, Clone)]
+pub struct TypeParameterUse {
+    pub parameter: String,
+    pub usage: TypeParameterUsage,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeParameterUsage {
+    Binary(TokenKind),
+    Unary(TokenKind),
+    Print,
+    /// Passed on as the named type parameter of another generic class or function.
+    Instantiate(String, String),
+}
+
+impl Gemstone {
+    pub fn type_checker(&mut self, exprs: &[Expr]) -> Result<(), Vec<Diagnostic>> {
+        self.resolve(exprs)?;
+        self.functions.clear();
+        self.expr_types.clear();
+        self.argument_conversions.clear();
+        self.range_types.clear();
+        self.type_parameter_uses.clear();
+        self.instantiations.clear();
+        self.requirement_checks.clear();
+        for expr in exprs {
+            if let ExprKind::Function(function) = &expr.kind {
+                if function.class_name.is_none() {
+                    self.functions.insert(function.name.clone(), function.clone());
+                }
+            }
+        }
+        self.type_check_method_definitions(exprs);
+        for expr in exprs {
+            if let Err(diagnostic) = self.type_check_expr(expr) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.type_check_requirements();
+        let diagnostics: Vec<Diagnostic> = std::mem::take(&mut self.diagnostics);
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+    /// Binds every `function Class::method()` to an `external` declaration in
+    /// `Class`, and makes sure each external method is defined exactly once.
+    fn type_check_method_definitions(&mut self, exprs: &[Expr]) {
+        let mut definitions: HashMap<(String, String), Span> = HashMap::new();
+        for expr in exprs {
+            let function: &Function = match &expr.kind {
+                ExprKind::Function(function) => function,
+                _ => continue,
+            };
+            let class_name: &String = match &function.class_name {
+                Some(class_name) => class_name,
+                None => continue,
+            };
+            let class: &Class = match self.classes.get(class_name) {
+                Some(class) => class,
+                None => {
+                    self.diagnostics.push(Diagnostic::error(codes::UNKNOWN_CLASS, expr.span, format!("unknown class `{}`", class_name)));
+                    continue;
+                }
+            };
+            if !class.type_params.is_empty() {
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_DEFINITION, expr.span, format!("methods of generic class `{}` cannot be defined outside of it", class_name)));
+                continue;
+            }
+            let declaration: Option<&ClassFunction> = class.methods.iter().find_map(|member| match &member.kind {
+                ExprKind::ClassFunction(class_function) if class_function.name == function.name => Some(class_function),
+                _ => None,
+            });
+            let declaration: &ClassFunction = match declaration {
+                Some(declaration) => declaration,
+                None => {
+                    self.diagnostics.push(Diagnostic::error(codes::UNKNOWN_MEMBER, expr.span, format!("class `{}` has no method `{}`", class_name, function.name)));
+                    continue;
+                }
+            };
+            if !declaration.is_external {
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_DEFINITION, expr.span, format!("method `{}::{}` is not declared external", class_name, function.name))
+                    .with_note("only `external` methods can be defined outside of their class"));
+                continue;
+            }
+            let declared_args: Vec<&Type> = declaration.args.iter().map(|arg| &arg.param_type).collect();
+            let args: Vec<&Type> = function.args.iter().map(|arg| &arg.param_type).collect();
+            if declared_args != args || declaration.return_type != function.return_type {
+                let declared: Vec<String> = declared_args.iter().map(|arg_type| arg_type.to_string()).collect();
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_DEFINITION, expr.span, format!("definition of `{}::{}` does not match its declaration", class_name, function.name))
+                    .with_note(format!("declared as `({}) -> {}`", declared.join(", "), declaration.return_type)));
+            }
+            if let Some(previous) = definitions.insert((class_name.clone(), function.name.clone()), expr.span) {
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_DEFINITION, expr.span, format!("method `{}::{}` is defined more than once", class_name, function.name))
+                    .with_note(format!("previous definition at {}:{}", previous.line, previous.column)));
+            }
+        }
+        for expr in exprs {
+            let class: &Class = match &expr.kind {
+                ExprKind::Class(class) => class,
+                _ => continue,
+            };
+            for member in &class.methods {
+                if let ExprKind::ClassFunction(class_function) = &member.kind {
+                    if class_function.is_external && !class.type_params.is_empty() {
+                        self.diagnostics.push(Diagnostic::error(codes::INVALID_DEFINITION, member.span, format!("method `{}::{}` of a generic class cannot be external", class.name, class_function.name))
+                            .with_note(format!("define `{}` in the body of `{}`", class_function.name, class.name)));
+                    } else if class_function.is_external && !definitions.contains_key(&(class.name.clone(), class_function.name.clone())) {
+                        self.diagnostics.push(Diagnostic::error(codes::MISSING_DEFINITION, member.span, format!("external method `{}::{}` is never defined", class.name, class_function.name))
+                            .with_note(format!("define it with `function {}::{}(...)`", class.name, class_function.name)));
+                    }
+                }
+            }
+        }
+    }
+    fn type_check_expr(&mut self, expr: &Expr) -> Result<(), Diagnostic> {
+        match &expr.kind {
+            ExprKind::Class(class) => self.type_check_class(expr.span, class),
+            ExprKind::Interface(interface) => self.type_check_interface(interface),
+            ExprKind::Function(function) => match &function.class_name {
+                Some(class_name) => {
+                    if !self.classes.get(class_name).is_some_and(|class| class.type_params.is_empty()) {
+                        return Ok(()); // reported by type_check_method_definitions
+                    }
+                    self.current_class = Some(class_name.clone());
+                    let result: Result<(), Diagnostic> = self.type_check_function(function);
+                    self.current_class = None;
+                    result
+                },
+                None => self.type_check_function(function),
+            },
+            ExprKind::Block(block) => {
+                // keep checking the rest of the block so one bad statement doesn't hide the others
+                for expr in &block.exprs {
+                    if let Err(diagnostic) = self.type_check_expr(expr) {
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+                Ok(())
+            },
+            ExprKind::VariableDeclaration(variable_declaration) => self.type_check_variable_declaration(expr.span, variable_declaration),
+            ExprKind::Return(value) => self.type_check_return(expr.span, value),
+            ExprKind::If(if_expr) => {
+                if let Err(diagnostic) = self.type_check_condition(&if_expr.condition) {
+                    self.diagnostics.push(diagnostic);
+                }
+                if let Err(diagnostic) = self.type_check_expr(&if_expr.then_branch) {
+                    self.diagnostics.push(diagnostic);
+                }
+                match &if_expr.else_branch {
+                    Some(else_branch) => self.type_check_expr(else_branch),
+                    None => Ok(()),
+                }
+            },
+            ExprKind::While(while_expr) => {
+                if let Err(diagnostic) = self.type_check_condition(&while_expr.condition) {
+                    self.diagnostics.push(diagnostic);
+                }
+                self.type_check_expr(&while_expr.body)
+            },
+            ExprKind::For(for_expr) => {
+                self.type_check_expr(&for_expr.initializer)?;
+                // an empty condition loops forever, like `for (;;)` in C
+                if !matches!(for_expr.condition.kind, ExprKind::Empty) {
+                    self.type_check_condition(&for_expr.condition)?;
+                }
+                self.type_checker_get_type(&for_expr.step)?;
+                self.type_check_expr(&for_expr.body)
+            },
+            ExprKind::ForRange(for_range) => {
+                let variable_type: Type = self.type_check_range_bounds(&for_range.start, &for_range.end)?;
+                self.range_types.insert(expr.span, variable_type);
+                self.type_check_expr(&for_range.body)
+            },
+            ExprKind::Break | ExprKind::Continue | ExprKind::Empty => Ok(()),
+            _ => self.type_checker_get_type(expr).map(|_| ()),
+        }
+    }
+    /// The bounds of a range loop share an integer type, which the loop variable
+    /// takes. A bound without a suffix takes the type of the other one, so
+    /// `0..values.length` counts in `usize`.
+    fn type_check_range_bounds(&mut self, start: &Expr, end: &Expr) -> Result<Type, Diagnostic> {
+        let start_type: Option<Type> = match unsuffixed_int_literal(start) {
+            Some(_) => None,
+            None => Some(self.type_checker_get_type(start)?),
+        };
+        let end_type: Option<Type> = match unsuffixed_int_literal(end) {
+            Some(_) => None,
+            None => Some(self.type_checker_get_type(end)?),
+        };
+        let variable_type: Type = match (start_type, end_type) {
+            (None, None) => Type::Int,
+            (Some(bound_type), None) | (None, Some(bound_type)) => bound_type,
+            (Some(start_type), Some(end_type)) => match self.numeric_common_type(&start_type, &end_type) {
+                Some(common) => common,
+                None => return Err(Diagnostic::error(codes::MISMATCHED_TYPES, start.span.to(&end.span), format!("mismatched types: the range starts at a `{}` but ends at a `{}`", start_type, end_type))
+                    .with_note("convert one of the bounds with `as`")),
+            },
+        };
+        if !variable_type.is_integer() {
+            return Err(Diagnostic::error(codes::MISMATCHED_TYPES, start.span.to(&end.span), format!("mismatched types: range bounds must be integers, found `{}`", variable_type)));
+        }
+        for bound in [start, end] {
+            let bound_type: Type = self.type_checker_get_type_expecting(bound, &variable_type)?;
+            self.type_check_compatible(bound.span, &variable_type, &bound_type)?;
+        }
+        Ok(variable_type)
+    }
+    fn type_check_class(&mut self, span: Span, class: &Class) -> Result<(), Diagnostic> {
+        if let Some(base_class) = &class.base_class {
+            let base_type_params: Vec<String> = match self.classes.get(base_class) {
+                Some(base) => base.type_params.clone(),
+                None if self.interfaces.contains_key(base_class) => return Err(Diagnostic::error(codes::INVALID_IMPLEMENTATION, span, format!("`{}` is an interface, not a class", base_class))
+                    .with_note(format!("implement it with `class {} implements {}`", class.name, base_class))),
+                None => return Err(Diagnostic::error(codes::UNKNOWN_CLASS, span, format!("unknown base class `{}`", base_class))),
+            };
+            if !base_type_params.is_empty() {
+                return Err(Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, format!("generic class `{}` cannot be a base class", base_class)));
+            }
+            // the generated C++ constructs the base with its default constructor
+            if !self.find_constructors(base_class).iter().any(|constructor| constructor.args.is_empty() && constructor.access == AccessModifier::Public) {
+                self.diagnostics.push(Diagnostic::error(codes::NO_MATCHING_CONSTRUCTOR, span, format!("base class `{}` has no public constructor without arguments", base_class))
+                    .with_note(format!("`{}` is constructed without arguments whenever `{}` is", base_class, class.name)));
+            }
+        }
+        self.type_check_type_parameters(span, &class.type_params);
+        for (position, interface_name) in class.interfaces.iter().enumerate() {
+            if class.interfaces[..position].contains(interface_name) {
+                self.diagnostics.push(Diagnostic::error(codes::DUPLICATE_DECLARATION, span, format!("interface `{}` is listed more than once", interface_name)));
+            } else if let Err(diagnostic) = self.type_check_implementation(span, class, interface_name) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.current_class = Some(class.name.clone());
+        self.generic_owner = (!class.type_params.is_empty()).then(|| class.name.clone());
+        let mut signatures: Vec<(Vec<&Type>, Span)> = vec![];
+        for member in &class.methods {
+            let result: Result<(), Diagnostic> = match &member.kind {
+                ExprKind::ClassVariable(class_variable) => self.type_check_class_variable(member.span, class_variable),
+                ExprKind::ClassFunction(class_function) => self.type_check_class_function(member.span, class, class_function),
+                ExprKind::Constructor(constructor) => {
+                    let signature: Vec<&Type> = constructor.args.iter().map(|arg| &arg.param_type).collect();
+                    if let Some((_, previous)) = signatures.iter().find(|(existing, _)| *existing == signature) {
+                        self.diagnostics.push(Diagnostic::error(codes::DUPLICATE_DECLARATION, member.span, format!("constructor `{}` is already declared", constructor_signature(&class.name, &constructor.args)))
+                            .with_note(format!("previous declaration at {}:{}", previous.line, previous.column)));
+                    }
+                    signatures.push((signature, member.span));
+                    self.type_check_constructor(constructor)
+                },
+                _ => Ok(()),
+            };
+            if let Err(diagnostic) = result {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.type_check_field_initialization(class);
+        self.current_class = None;
+        self.generic_owner = None;
+        Ok(())
+    }
+    fn type_check_interface(&mut self, interface: &Interface) -> Result<(), Diagnostic> {
+        let mut names: Vec<(&String, Span)> = vec![];
+        for member in &interface.methods {
+            let method: &ClassFunction = match &member.kind {
+                ExprKind::ClassFunction(method) => method,
+                _ => continue,
+            };
+            if let Some((_, previous)) = names.iter().find(|(name, _)| **name == method.name) {
+                self.diagnostics.push(Diagnostic::error(codes::DUPLICATE_DECLARATION, member.span, format!("method `{}` is already declared in interface `{}`", method.name, interface.name))
+                    .with_note(format!("previous declaration at {}:{}", previous.line, previous.column)));
+            }
+            names.push((&method.name, member.span));
+            for arg in &method.args {
+                self.type_check_type(member.span, &arg.param_type)?;
+            }
+            self.type_check_type(member.span, &method.return_type)?;
+        }
+        Ok(())
+    }
+    /// Every method of the interface must be a public method of the class, declared
+    /// in it or inherited, with the same parameter and return types.
+    fn type_check_implementation(&mut self, span: Span, class: &Class, interface_name: &str) -> Result<(), Diagnostic> {
+        let interface: Interface = match self.interfaces.get(interface_name) {
+            Some(interface) => interface.clone(),
+            None if self.classes.contains_key(interface_name) => return Err(Diagnostic::error(codes::INVALID_IMPLEMENTATION, span, format!("`{}` is a class, not an interface", interface_name))
+                .with_note(format!("derive from it with `class {}: {}`", class.name, interface_name))),
+            None => return Err(Diagnostic::error(codes::UNKNOWN_CLASS, span, format!("unknown interface `{}`", interface_name))),
+        };
+        for member in &interface.methods {
+            let required: &ClassFunction = match &member.kind {
+                ExprKind::ClassFunction(required) => required,
+                _ => continue,
+            };
+            let required_args: Vec<&Type> = required.args.iter().map(|arg| &arg.param_type).collect();
+            let required_types: Vec<String> = required_args.iter().map(|arg_type| arg_type.to_string()).collect();
+            let declared_as: String = format!("declared in `{}` as `({}) -> {}`", interface.name, required_types.join(", "), required.return_type);
+            let method: ClassFunction = match self.find_method(&class.name, &required.name) {
+                Some(method) => method,
+                None => {
+                    self.diagnostics.push(Diagnostic::error(codes::INVALID_IMPLEMENTATION, span, format!("class `{}` does not implement `{}::{}`", class.name, interface.name, required.name))
+                        .with_note(declared_as));
+                    continue;
+                },
+            };
+            let args: Vec<&Type> = method.args.iter().map(|arg| &arg.param_type).collect();
+            if args != required_args || method.return_type != required.return_type {
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_IMPLEMENTATION, span, format!("method `{}` of class `{}` does not match `{}::{}`", required.name, class.name, interface.name, required.name))
+                    .with_note(declared_as));
+            } else if method.access == AccessModifier::Private {
+                self.diagnostics.push(Diagnostic::error(codes::INVALID_IMPLEMENTATION, span, format!("method `{}` of class `{}` is private", required.name, class.name))
+                    .with_note(format!("it implements `{}::{}`, so it must be public", interface.name, required.name)));
+            }
+        }
+        Ok(())
+    }
+    fn type_check_type_parameters(&mut self, span: Span, type_params: &[String]) {
+        for (position, type_param) in type_params.iter().enumerate() {
+            if type_params[..position].contains(type_param) {
+                self.diagnostics.push(Diagnostic::error(codes::DUPLICATE_DECLARATION, span, format!("type parameter `{}` is declared more than once", type_param)));
+            }
+        }
+    }
+    /// Scalar fields hold garbage until they are assigned, so each one without a
+    /// default must be set by the generated constructor or by every declared one.
+    fn type_check_field_initialization(&mut self, class: &Class) {
+        let constructors: Vec<(&Constructor, Span)> = class.methods.iter().filter_map(|member| match &member.kind {
+            ExprKind::Constructor(constructor) => Some((constructor, member.span)),
+            _ => None,
+        }).collect();
+        for member in &class.methods {
+            let field: &ClassVariable = match &member.kind {
+                ExprKind::ClassVariable(field) if matches!(field.initializer.kind, ExprKind::Empty) && is_scalar(&field.var_type) => field,
+                _ => continue,
+            };
+            if constructors.is_empty() {
+                // the generated constructor takes the public fields
+                if field.access == AccessModifier::Private {
+                    self.diagnostics.push(Diagnostic::error(codes::UNINITIALIZED_FIELD, member.span, format!("private field `{}` is never initialized", field.name))
+                        .with_note(format!("give it a default value, as in `private var {}: {} = ...;`, or declare a constructor that assigns it", field.name, field.var_type)));
+                }
+                continue;
+            }
+            for (constructor, span) in &constructors {
+                if !self.initializes_field(&constructor.body, &field.name) {
+                    self.diagnostics.push(Diagnostic::error(codes::UNINITIALIZED_FIELD, *span, format!("constructor `{}` does not initialize field `{}`", constructor_signature(&class.name, &constructor.args), field.name))
+                        .with_note(format!("assign `{}` on every path through the constructor, or give it a default value", field.name)));
+                }
+            }
+        }
+    }
+    /// Whether running `expr` to completion always assigns the field `name`.
+    fn initializes_field(&self, expr: &Expr, name: &str) -> bool {
+        match &expr.kind {
+            ExprKind::Assignment(target, TokenKind::Equal, value) => {
+                let assigns_field: bool = matches!(&target.kind, ExprKind::Variable(target_name) if target_name == name)
+                    && self.bindings.get(&target.span).is_some_and(|symbol| symbol.kind == SymbolKind::Field);
+                assigns_field || self.initializes_field(value, name)
+            },
+            ExprKind::Block(block) => {
+                for statement in &block.exprs {
+                    if self.initializes_field(statement, name) {
+                        return true;
+                    }
+                    // an early return leaves the rest of the constructor unrun
+                    if let ExprKind::Return(_) = statement.kind {
+                        return false;
+                    }
+                }
+                false
+            },
+            ExprKind::If(if_expr) => match &if_expr.else_branch {
+                Some(else_branch) => self.initializes_field(&if_expr.then_branch, name) && self.initializes_field(else_branch, name),
+                None => false,
+            },
+            ExprKind::For(for_expr) => self.initializes_field(&for_expr.initializer, name),
+            // a loop body might never run
+            _ => false,
+        }
+    }
+    fn type_check_class_variable(&mut self, span: Span, class_variable: &ClassVariable) -> Result<(), Diagnostic> {
+        self.type_check_type(span, &class_variable.var_type)?;
+        if let ExprKind::Empty = class_variable.initializer.kind {
+            return Ok(());
+        }
+        let value_type: Type = self.type_checker_get_type_expecting(&class_variable.initializer, &class_variable.var_type)?;
+        self.type_check_compatible(class_variable.initializer.span, &class_variable.var_type, &value_type)
+    }
+    fn type_check_class_function(&mut self, span: Span, class: &Class, class_function: &ClassFunction) -> Result<(), Diagnostic> {
+        if class_function.is_override {
+            self.type_check_override(span, class, class_function)?;
+        }
+        for arg in &class_function.args {
+            self.type_check_type(class_function.body.span, &arg.param_type)?;
+        }
+        self.type_check_type(class_function.body.span, &class_function.return_type)?;
+        self.return_type = class_function.return_type.clone();
+        self.type_check_expr(&class_function.body)?;
+        self.type_check_returns(&class_function.name, &class_function.return_type, &class_function.body)
+    }
+    fn type_check_constructor(&mut self, constructor: &Constructor) -> Result<(), Diagnostic> {
+        for arg in &constructor.args {
+            self.type_check_type(constructor.body.span, &arg.param_type)?;
+        }
+        self.return_type = Type::Void;
+        self.type_check_expr(&constructor.body)
+    }
+    /// An `override` method must replace a virtual method of a base class or an
+    /// interface method with the same signature.
+    fn type_check_override(&mut self, span: Span, class: &Class, class_function: &ClassFunction) -> Result<(), Diagnostic> {
+        let bases: Vec<&String> = class.base_class.iter().chain(&class.interfaces).collect();
+        let base: ClassFunction = match bases.iter().find_map(|base| self.find_method(base, &class_function.name)) {
+            Some(base) => base,
+            None => return Err(Diagnostic::error(codes::INVALID_OVERRIDE, span, format!("method `{}` is marked override but does not override a base class method", class_function.name))),
+        };
+        if !bases.iter().any(|base| self.is_overridable(base, &class_function.name)) {
+            return Err(Diagnostic::error(codes::INVALID_OVERRIDE, span, format!("cannot override non-virtual method `{}`", class_function.name))
+                .with_note("mark the base class method `virtual` to allow overriding it"));
+        }
+        let base_args: Vec<&Type> = base.args.iter().map(|arg| &arg.param_type).collect();
+        let args: Vec<&Type> = class_function.args.iter().map(|arg| &arg.param_type).collect();
+        if base_args != args || base.return_type != class_function.return_type {
+            return Err(Diagnostic::error(codes::INVALID_OVERRIDE, span, format!("method `{}` does not match the signature of the method it overrides", class_function.name)));
+        }
+        Ok(())
+    }
+    /// A generic function is checked once, with its type parameters standing for
+    /// types that support whatever the body does with them.
+    fn type_check_function(&mut self, function: &Function) -> Result<(), Diagnostic> {
+        self.type_check_type_parameters(function.body.span, &function.type_params);
+        for arg in &function.args {
+            self.type_check_type(function.body.span, &arg.param_type)?;
+        }
+        self.type_check_type(function.body.span, &function.return_type)?;
+        self.return_type = function.return_type.clone();
+        self.generic_owner = (!function.type_params.is_empty()).then(|| function.name.clone());
+        let result: Result<(), Diagnostic> = self.type_check_expr(&function.body);
+        self.generic_owner = None;
+        result?;
+        self.type_check_returns(&function.name, &function.return_type, &function.body)
+    }
+    /// A function returning a value must not fall off the end of its body, which
+    /// C++ would let through as undefined behaviour.
+    fn type_check_returns(&mut self, name: &str, return_type: &Type, body: &Expr) -> Result<(), Diagnostic> {
+        if *return_type == Type::Void || !matches!(body.kind, ExprKind::Block(_)) || always_returns(body) {
+            return Ok(());
+        }
+        Err(Diagnostic::error(codes::MISMATCHED_TYPES, body.span, format!("not every path through `{}` returns a value", name))
+            .with_note(format!("`{}` returns `{}`; add a `return` at the end of the body or to every branch that reaches it", name, return_type)))
+    }
+    fn type_check_variable_declaration(&mut self, span: Span, variable_declaration: &VariableDeclaration) -> Result<(), Diagnostic> {
+        let var_type: Type = variable_declaration.var_type.clone();
+        self.type_check_type(span, &var_type)?;
+        let value: &Expr = &variable_declaration.value;
+        let value_type: Type = self.type_checker_get_type_expecting(value, &var_type)?;
+        self.type_check_compatible(value.span, &var_type, &value_type)
+    }
+    fn type_check_condition(&mut self, condition: &Expr) -> Result<(), Diagnostic> {
+        let condition_type: Type = self.type_checker_get_type(condition)?;
+        if condition_type != Type::Bool {
+            return Err(Diagnostic::error(codes::MISMATCHED_TYPES, condition.span, format!("mismatched types: expected `bool`, found `{}`", condition_type))
+                .with_note("conditions are not implicitly converted to `bool`"));
+        }
+        Ok(())
+    }
+    fn type_check_return(&mut self, span: Span, value: &Expr) -> Result<(), Diagnostic> {
+        let return_type: Type = self.return_type.clone();
+        if let ExprKind::Empty = value.kind {
+            if return_type != Type::Void {
+                return Err(Diagnostic::error(codes::MISMATCHED_TYPES, span, format!("expected a value of type `{}` to be returned", return_type)));
+            }
+            return Ok(());
+        }
+        let value_type: Type = self.type_checker_get_type_expecting(value, &return_type)?;
+        if return_type == Type::Void {
+            return Err(Diagnostic::error(codes::MISMATCHED_TYPES, value.span, "cannot return a value from a function returning `void`"));
+        }
+        self.type_check_compatible(value.span, &return_type, &value_type)
+    }
+    /// Makes sure every class named by `type_` has been declared, with the type
+    /// arguments it takes.
+    fn type_check_type(&mut self, span: Span, type_: &Type) -> Result<(), Diagnostic> {
+        match type_ {
+            // like abstract classes in C++, interfaces have no values of their own
+            Type::Pointer(inner) if matches!(&**inner, Type::Class(name, type_args) if type_args.is_empty() && self.interfaces.contains_key(name)) => Ok(()),
+            Type::Class(name, type_args) if self.interfaces.contains_key(name) => {
+                self.type_check_type_argument_count(span, name, &[], type_args.len())?;
+                Err(Diagnostic::error(codes::MISMATCHED_TYPES, span, format!("interface `{}` can only be used behind a pointer", name))
+                    .with_note(format!("use `{}*`", name)))
+            },
+            Type::Class(name, type_args) => {
+                let type_params: Vec<String> = match self.classes.get(name) {
+                    Some(class) => class.type_params.clone(),
+                    None => return Err(Diagnostic::error(codes::UNKNOWN_CLASS, span, format!("unknown type `{}`", name))),
+                };
+                self.type_check_type_argument_count(span, name, &type_params, type_args.len())?;
+                for type_arg in type_args {
+                    self.type_check_type_argument(span, type_arg)?;
+                }
+                self.type_check_instance(span, name, &type_params, type_args);
+                Ok(())
+            },
+            Type::Pointer(inner) | Type::Array(inner, _) | Type::Slice(inner) => self.type_check_type(span, inner),
+            _ => Ok(()),
+        }
+    }
+    fn type_check_type_argument(&mut self, span: Span, type_arg: &Type) -> Result<(), Diagnostic> {
+        if *type_arg == Type::Void {
+            return Err(Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, "`void` cannot be a type argument"));
+        }
+        self.type_check_type(span, type_arg)
+    }
+    fn type_check_type_argument_count(&self, span: Span, name: &str, type_params: &[String], supplied: usize) -> Result<(), Diagnostic> {
+        if type_params.len() == supplied {
+            return Ok(());
+        }
+        let signature: String = format!("{}<{}>", name, type_params.join(", "));
+        Err(match (type_params.len(), supplied) {
+            (0, _) => Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, format!("`{}` is not generic", name)),
+            (_, 0) => Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, format!("missing type arguments for `{}`", name))
+                .with_note(format!("`{}` is declared as `{}`", name, signature)),
+            (expected, _) => Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, format!("`{}` takes {} type argument{} but {} {} supplied", name, expected, if expected == 1 { "" } else { "s" }, supplied, if supplied == 1 { "was" } else { "were" }))
+                .with_note(format!("`{}` is declared as `{}`", name, signature)),
+        })
+    }
+    /// The type arguments of a call to the generic function or constructor `name`:
+    /// the ones written out, or else the ones inferred from the arguments.
+    fn type_check_type_arguments(&mut self, span: Span, name: &str, type_params: &[String], written: &[Type], args: &[Expr], parameters: &[Parameter]) -> Result<Vec<Type>, Diagnostic> {
+        let type_args: Vec<Type> = if written.is_empty() && !type_params.is_empty() {
+            self.infer_type_arguments(span, name, type_params, args, parameters)?
+        } else {
+            self.type_check_type_argument_count(span, name, type_params, written.len())?;
+            for type_arg in written {
+                self.type_check_type_argument(span, type_arg)?;
+            }
+            written.to_vec()
+        };
+        if !type_args.is_empty() {
+            self.type_check_instance(span, name, type_params, &type_args);
+            self.instantiations.insert(span, type_args.clone());
+        }
+        Ok(type_args)
+    }
+    /// Matches the types of the arguments against the parameters. Arguments whose type
+    /// is known on its own go first, so `max(x, 1)` gives the literal the type of `x`.
+    fn infer_type_arguments(&mut self, span: Span, name: &str, type_params: &[String], args: &[Expr], parameters: &[Parameter]) -> Result<Vec<Type>, Diagnostic> {
+        let mut inferred: Vec<Option<Type>> = vec![None; type_params.len()];
+        if !args.is_empty() {
+            self.type_check_labels(span, name, args, parameters)?;
+        }
+        for literals in [false, true] {
+            for (arg, parameter) in args.iter().zip(parameters) {
+                let value: &Expr = argument_value(arg);
+                let is_literal: bool = unsuffixed_int_literal(value).is_some() || matches!(value.kind, ExprKind::Float(_, None));
+                if is_literal != literals || !parameter.param_type.has_parameters() {
+                    continue;
+                }
+                let arg_type: Type = self.type_checker_get_type(value)?;
+                unify(&parameter.param_type, &arg_type, type_params, &mut inferred);
+            }
+        }
+        let mut type_args: Vec<Type> = vec![];
+        for (type_param, type_arg) in type_params.iter().zip(inferred) {
+            match type_arg {
+                Some(type_arg) => type_args.push(type_arg),
+                None => return Err(Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, span, format!("cannot infer the type of `{}` for `{}`", type_param, name))
+                    .with_note(format!("write the type arguments out, as in `{}<{}>`", name, type_params.join(", ")))),
+            }
+        }
+        Ok(type_args)
+    }
+    /// Records that `name` is used with `type_args`, to be checked against what its
+    /// body does with them once every body has been checked. A type parameter of the
+    /// generic being checked passes its own requirements on instead.
+    fn type_check_instance(&mut self, span: Span, name: &str, type_params: &[String], type_args: &[Type]) {
+        for (type_param, type_arg) in type_params.iter().zip(type_args) {
+            match type_arg {
+                Type::Parameter(parameter) => {
+                    if self.generic_owner.as_deref() == Some(name) && parameter == type_param {
+                        continue;
+                    }
+                    self.use_type_parameter(parameter, TypeParameterUsage::Instantiate(name.to_string(), type_param.clone()), span);
+                },
+                _ => self.requirement_checks.push((name.to_string(), type_param.clone(), type_arg.clone(), span)),
+            }
+        }
+    }
+    fn use_type_parameter(&mut self, parameter: &str, usage: TypeParameterUsage, span: Span) {
+        if let Some(owner) = self.generic_owner.clone() {
+            self.type_parameter_uses.entry(owner).or_default().push(TypeParameterUse { parameter: parameter.to_string(), usage, span });
+        }
+    }
+    fn type_check_requirements(&mut self) {
+        let checks: Vec<(String, String, Type, Span)> = std::mem::take(&mut self.requirement_checks);
+        for (position, (generic, type_param, type_arg, span)) in checks.iter().enumerate() {
+            if checks[..position].iter().any(|check| check == &checks[position]) {
+                continue;
+            }
+            let (owner, type_parameter_use): (String, TypeParameterUse) = match self.unsatisfied_use(generic, type_param, type_arg, &mut vec![]) {
+                Some(unsatisfied) => unsatisfied,
+                None => continue,
+            };
+            let usage: String = match &type_parameter_use.usage {
+                TypeParameterUsage::Binary(op) | TypeParameterUsage::Unary(op) => format!("applies {} to", op),
+                TypeParameterUsage::Print => "prints a".to_string(),
+                TypeParameterUsage::Instantiate(..) => unreachable!("instantiations are followed"),
+            };
+            self.diagnostics.push(Diagnostic::error(codes::INVALID_TYPE_ARGUMENT, *span, format!("`{}` cannot be used as `{}` in `{}`", type_arg, type_param, generic))
+                .with_note(format!("`{}` {} `{}` at {}:{}", owner, usage, type_parameter_use.parameter, type_parameter_use.span.line, type_parameter_use.span.column)));
+        }
+    }
+    /// The first use of `type_param` in the body of `generic`, or of a generic it
+    /// passes `type_param` on to, that `type_arg` does not support.
+    fn unsatisfied_use(&self, generic: &str, type_param: &str, type_arg: &Type, visited: &mut Vec<(String, String)>) -> Option<(String, TypeParameterUse)> {
+        if visited.contains(&(generic.to_string(), type_param.to_string())) {
+            return None;
+        }
+        visited.push((generic.to_string(), type_param.to_string()));
+        for type_parameter_use in self.type_parameter_uses.get(generic)?.iter().filter(|type_parameter_use| type_parameter_use.parameter == type_param) {
+            let supported: bool = match &type_parameter_use.usage {
+                TypeParameterUsage::Binary(op) => self.binary_op_type(type_arg, op, type_arg).is_some(),
+                TypeParameterUsage::Unary(op) => unary_op_type(op, type_arg).is_some(),
+                TypeParameterUsage::Print => is_printable(type_arg),
+                TypeParameterUsage::Instantiate(other, other_param) => match self.unsatisfied_use(other, other_param, type_arg, visited) {
+                    Some(unsatisfied) => return Some(unsatisfied),
+                    None => true,
+                },
+            };
+            if !supported {
+                return Some((generic.to_string(), type_parameter_use.clone()));
+            }
+        }
+        None
+    }
+    /// Checks that a value of type `found` can be stored where `expected` is required.
+    fn type_check_compatible(&mut self, span: Span, expected: &Type, found: &Type) -> Result<(), Diagnostic> {
+        if self.type_is_compatible(expected, found) {
+            return Ok(());
+        }
+        let diagnostic: Diagnostic = Diagnostic::error(codes::MISMATCHED_TYPES, span, format!("mismatched types: expected `{}`, found `{}`", expected, found));
+        if expected.is_numeric() && found.is_numeric() {
+            return Err(diagnostic.with_note(format!("`{}` may not hold every `{}` exactly; convert with `as {}`", expected, found, expected)));
+        }
+        Err(diagnostic)
+    }
+    /// Integers widen to integer types that hold all of their values and to floating
+    /// point types that represent all of them exactly, and `float` widens to `f64`;
+    /// anything narrower needs a cast.
+    fn type_is_compatible(&self, expected: &Type, found: &Type) -> bool {
+        match (expected, found) {
+            _ if expected == found => true,
+            (Type::Float | Type::F64, _) => match found.integer_range() {
+                // the integers `float` and `f64` hold exactly are those within their mantissa
+                Some((found_min, found_max)) => {
+                    let limit: i128 = if *expected == Type::Float { 1 << f32::MANTISSA_DIGITS } else { 1 << f64::MANTISSA_DIGITS };
+                    -limit <= found_min && found_max <= limit
+                },
+                None => *expected == Type::F64 && *found == Type::Float,
+            },
+            // string literals decay to `char*` like they do in C++
+            (Type::Pointer(inner), Type::String) => **inner == Type::Char,
+            (Type::Pointer(_), Type::Null) => true,
+            (Type::Pointer(expected), Type::Pointer(found)) => match (&**expected, &**found) {
+                // generic classes are never base classes
+                (Type::Class(expected, expected_args), Type::Class(found, _)) if expected_args.is_empty() => self.is_subclass(found, expected),
+                _ => expected == found,
+            },
+            _ => match (expected.integer_range(), found.integer_range()) {
+                (Some((expected_min, expected_max)), Some((found_min, found_max))) => expected_min <= found_min && found_max <= expected_max,
+                _ => false,
+            },
+        }
+    }
+    /// Whether `class` is `base`, derives from it or implements it.
+    fn is_subclass(&self, class: &str, base: &str) -> bool {
+        let mut current: Option<String> = Some(class.to_string());
+        while let Some(name) = current {
+            if name == base {
+                return true;
+            }
+            let class: Option<&Class> = self.classes.get(&name);
+            if class.is_some_and(|class| class.interfaces.iter().any(|interface| interface == base)) {
+                return true;
+            }
+            current = class.and_then(|class| class.base_class.clone());
+        }
+        false
+    }
+    fn type_checker_get_type(&mut self, expr: &Expr) -> Result<Type, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Int(int, suffix) => self.type_check_int_literal(expr.span, *int, &literal_type(suffix.as_deref(), Type::Int), false),
+            ExprKind::Float(float, suffix) => self.type_check_float_literal(expr.span, *float, &literal_type(suffix.as_deref(), Type::Float)),
+            ExprKind::Bool(_) => Ok(Type::Bool),
+            ExprKind::Char(_) => Ok(Type::Char),
+            ExprKind::String(_) => Ok(Type::String),
+            ExprKind::Null => Ok(Type::Null),
+            ExprKind::Array(elements) => self.type_check_array(expr.span, elements, None),
+            ExprKind::ArrayRepeat(value, length) => self.type_check_array_repeat(expr.span, value, *length, None),
+            ExprKind::Index(sequence, position) => self.type_check_index(sequence, position),
+            ExprKind::Empty => Ok(Type::Void),
+            ExprKind::Variable(name) => match self.bindings.get(&expr.span) {
+                // the variable of a range loop takes its type from the bounds, which the resolver doesn't know
+                Some(symbol) => Ok(self.range_types.get(&symbol.span).unwrap_or(&symbol.symbol_type).clone()),
+                None => Err(Diagnostic::error(codes::UNDEFINED_NAME, expr.span, format!("cannot find value `{}` in this scope", name))),
+            },
+            ExprKind::New(new) => self.type_check_new(expr.span, new),
+            ExprKind::UnaryOp(op, operand) => {
+                let result_type: Type = self.type_check_unary_op(expr.span, op, operand)?;
+                self.expr_types.insert(expr.span, result_type.clone());
+                Ok(result_type)
+            },
+            ExprKind::BinaryOp(left, op, right) => {
+                let result_type: Type = self.type_check_binary_op(expr.span, left, op, right)?;
+                self.expr_types.insert(expr.span, result_type.clone());
+                Ok(result_type)
+            },
+            ExprKind::Cast(value, target) => self.type_check_cast(expr.span, value, target),
+            ExprKind::Downcast(class_name, value) => self.type_check_downcast(expr.span, class_name, value),
+            ExprKind::InternalFunctionCall(function_call) => {
+                if let Some(arg) = function_call.args.iter().find(|arg| matches!(arg.kind, ExprKind::NamedArgument(..))) {
+                    return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, format!("`{}` does not take labelled arguments", function_call.name)));
+                }
+                self.type_check_type_argument_count(expr.span, &function_call.name, &[], function_call.type_args.len())?;
+                if function_call.args.len() != 1 {
+                    return Err(Diagnostic::error(codes::WRONG_ARGUMENT_COUNT, expr.span, format!("`{}` takes 1 argument but {} {} supplied", function_call.name, function_call.args.len(), if function_call.args.len() == 1 { "was" } else { "were" })));
+                }
+                let arg_type: Type = self.type_checker_get_type(&function_call.args[0])?;
+                match &arg_type {
+                    Type::Parameter(parameter) => {
+                        self.use_type_parameter(parameter, TypeParameterUsage::Print, function_call.args[0].span);
+                        Ok(Type::Void)
+                    },
+                    _ if is_printable(&arg_type) => Ok(Type::Void),
+                    _ => Err(Diagnostic::error(codes::MISMATCHED_TYPES, function_call.args[0].span, format!("`{}` cannot be printed", arg_type))),
+                }
+            },
+            ExprKind::FunctionCall(function_call) if self.bindings.get(&expr.span).is_some_and(|symbol| symbol.kind == SymbolKind::Method) => {
+                let class_name: String = self.current_class.clone().unwrap_or_default();
+                let method: ClassFunction = match self.find_method(&class_name, &function_call.name) {
+                    Some(method) => method,
+                    None => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, expr.span, format!("no method named `{}` on class `{}`", function_call.name, class_name))),
+                };
+                self.type_check_arguments(expr.span, &function_call.name, &function_call.args, &method.args)?;
+                Ok(method.return_type)
+            },
+            ExprKind::FunctionCall(function_call) => {
+                let function: Function = match self.functions.get(&function_call.name) {
+                    Some(function) => function.clone(),
+                    None => return Err(Diagnostic::error(codes::UNDEFINED_NAME, expr.span, format!("cannot find function `{}` in this scope", function_call.name))),
+                };
+                let type_args: Vec<Type> = self.type_check_type_arguments(expr.span, &function_call.name, &function.type_params, &function_call.type_args, &function_call.args, &function.args)?;
+                let parameters: Vec<Parameter> = substitute_parameters(&function.args, &function.type_params, &type_args);
+                self.type_check_arguments(expr.span, &function_call.name, &function_call.args, &parameters)?;
+                Ok(function.return_type.substitute(&function.type_params, &type_args))
+            },
+            ExprKind::MemberFunctionCall(parent, function_call) => self.type_check_method_call(expr.span, parent, function_call, false),
+            ExprKind::PointerMemberFunctionCall(parent, function_call) => self.type_check_method_call(expr.span, parent, function_call, true),
+            ExprKind::Member(parent, name) => {
+                let parent_type: Type = self.type_checker_get_type(parent)?;
+                match &parent_type {
+                    Type::Class(class_name, type_args) => self.type_check_field(expr.span, class_name, type_args, name),
+                    Type::Array(..) | Type::Slice(_) if name == "length" => {
+                        self.expr_types.insert(parent.span, parent_type.clone());
+                        Ok(Type::Usize)
+                    },
+                    Type::Array(..) | Type::Slice(_) => Err(Diagnostic::error(codes::UNKNOWN_MEMBER, expr.span, format!("`{}` has no field `{}`", parent_type, name))
+                        .with_note("the only field of arrays and slices is `length`")),
+                    Type::Pointer(inner) if matches!(**inner, Type::Class(..)) => Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` is a pointer", parent_type))
+                        .with_note(format!("use `->{}` to reach the field through the pointer", name))),
+                    _ => Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` has no fields", parent_type))),
+                }
+            },
+            ExprKind::PointerMember(parent, name) => {
+                let parent_type: Type = self.type_checker_get_type(parent)?;
+                match &parent_type {
+                    Type::Pointer(inner) => match &**inner {
+                        Type::Class(class_name, type_args) => self.type_check_field(expr.span, class_name, type_args, name),
+                        _ => Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` has no fields", parent_type))),
+                    },
+                    _ => Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`->` needs a pointer to a class, found `{}`", parent_type))),
+                }
+            },
+            ExprKind::Assignment(target, op, value) => self.type_check_assignment(expr.span, target, op, value),
+            ExprKind::NamedArgument(..) => Err(Diagnostic::error(codes::INVALID_LABEL, expr.span, "labelled arguments are only allowed in calls")),
+            _ => Err(Diagnostic::error(codes::UNSUPPORTED_EXPRESSION, expr.span, "this is not an expression")),
+        }
+    }
+    /// A method call on a class value (`value.method()`) or through a pointer to
+    /// one (`pointer->method()`).
+    fn type_check_method_call(&mut self, span: Span, parent: &Expr, function_call: &FunctionCall, through_pointer: bool) -> Result<Type, Diagnostic> {
+        let parent_type: Type = self.type_checker_get_type(parent)?;
+        let (class_name, type_args): (String, Vec<Type>) = match (&parent_type, through_pointer) {
+            (Type::Pointer(inner), true) => match &**inner {
+                Type::Class(class_name, type_args) => (class_name.clone(), type_args.clone()),
+                _ => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` has no methods", parent_type))),
+            },
+            (_, true) => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`->` needs a pointer to a class, found `{}`", parent_type))),
+            (Type::Class(class_name, type_args), false) => (class_name.clone(), type_args.clone()),
+            (Type::Pointer(inner), false) if matches!(**inner, Type::Class(..)) => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` is a pointer", parent_type))
+                .with_note(format!("use `->{}()` to call the method through the pointer", function_call.name))),
+            (_, false) => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, parent.span, format!("`{}` has no methods", parent_type))),
+        };
+        let method: ClassFunction = match self.find_method(&class_name, &function_call.name) {
+            Some(method) => method,
+            None => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, span, format!("no method named `{}` on class `{}`", function_call.name, class_name))),
+        };
+        if method.access == AccessModifier::Private && self.current_class.as_deref() != Some(class_name.as_str()) {
+            return Err(Diagnostic::error(codes::PRIVATE_MEMBER, span, format!("method `{}` of class `{}` is private", function_call.name, class_name)));
+        }
+        let type_params: Vec<String> = self.classes.get(&class_name).map(|class| class.type_params.clone()).unwrap_or_default();
+        let parameters: Vec<Parameter> = substitute_parameters(&method.args, &type_params, &type_args);
+        self.type_check_arguments(span, &function_call.name, &function_call.args, &parameters)?;
+        Ok(method.return_type.substitute(&type_params, &type_args))
+    }
+    /// Picks the constructor `new` calls the way C++ overload resolution would for
+    /// Gemstone's types: an exact match wins, otherwise exactly one constructor
+    /// must accept the arguments after conversions.
+    fn type_check_new(&mut self, span: Span, new: &New) -> Result<Type, Diagnostic> {
+        let type_params: Vec<String> = match self.classes.get(&new.class_name) {
+            Some(class) => class.type_params.clone(),
+            None if self.interfaces.contains_key(&new.class_name) => return Err(Diagnostic::error(codes::NO_MATCHING_CONSTRUCTOR, span, format!("cannot create an instance of interface `{}`", new.class_name))
+                .with_note("create an object of a class that implements it instead")),
+            None => return Err(Diagnostic::error(codes::UNKNOWN_CLASS, span, format!("unknown class `{}`", new.class_name))),
+        };
+        let constructors: Vec<Constructor> = self.find_constructors(&new.class_name);
+        // the type arguments can only be inferred when it is known which constructor is called
+        let type_args: Vec<Type> = match constructors.as_slice() {
+            [constructor] => self.type_check_type_arguments(span, &new.class_name, &type_params, &new.type_args, &new.args, &constructor.args)?,
+            _ => self.type_check_type_arguments(span, &new.class_name, &type_params, &new.type_args, &[], &[])?,
+        };
+        let constructors: Vec<Constructor> = constructors.into_iter()
+            .map(|constructor| Constructor { args: substitute_parameters(&constructor.args, &type_params, &type_args), ..constructor })
+            .collect();
+        // with a single candidate, say exactly what is wrong with the arguments
+        if let [constructor] = constructors.as_slice() {
+            self.type_check_arguments(span, &new.class_name, &new.args, &constructor.args)?;
+        }
+        let mut arg_types: Vec<Type> = vec![];
+        for (position, arg) in new.args.iter().enumerate() {
+            let arg_type: Type = match constructors.as_slice() {
+                [constructor] if position < constructor.args.len() => self.type_checker_get_type_expecting(argument_value(arg), &constructor.args[position].param_type)?,
+                _ => self.type_checker_get_type(argument_value(arg))?,
+            };
+            arg_types.push(arg_type);
+        }
+        let accepts = |constructor: &&Constructor, exact: bool| self.type_check_labels(span, &new.class_name, &new.args, &constructor.args).is_ok()
+            && constructor.args.iter().zip(&arg_types).zip(&new.args).all(|((parameter, arg_type), arg)| if exact {
+                parameter.param_type == *arg_type
+            } else {
+                self.type_is_compatible(&parameter.param_type, arg_type) || literal_fits(argument_value(arg), &parameter.param_type)
+            });
+        let exact: Vec<&Constructor> = constructors.iter().filter(|constructor| accepts(constructor, true)).collect();
+        let compatible: Vec<&Constructor> = constructors.iter().filter(|constructor| accepts(constructor, false)).collect();
+        let candidates: Vec<String> = constructors.iter().map(|constructor| format!("`{}`", constructor_signature(&new.class_name, &constructor.args))).collect();
+        let constructor: &Constructor = match (exact.as_slice(), compatible.as_slice()) {
+            ([constructor], _) | (_, [constructor]) => constructor,
+            (_, []) => {
+                let found: Vec<String> = arg_types.iter().map(|arg_type| arg_type.to_string()).collect();
+                return Err(Diagnostic::error(codes::NO_MATCHING_CONSTRUCTOR, span, format!("no constructor of `{}` takes `({})`", new.class_name, found.join(", ")))
+                    .with_note(format!("the candidates are {}", candidates.join(", "))));
+            },
+            _ => return Err(Diagnostic::error(codes::NO_MATCHING_CONSTRUCTOR, span, format!("call to the constructor of `{}` is ambiguous", new.class_name))
+                .with_note(format!("the candidates are {}", candidates.join(", ")))),
+        };
+        if constructor.access == AccessModifier::Private && self.current_class.as_deref() != Some(new.class_name.as_str()) {
+            return Err(Diagnostic::error(codes::PRIVATE_MEMBER, span, format!("constructor `{}` is private", constructor_signature(&new.class_name, &constructor.args))));
+        }
+        // C++ ranks conversions differently and may find the call ambiguous, so the
+        // arguments are converted to the chosen constructor's parameters up front
+        if constructors.len() > 1 {
+            for ((parameter, arg_type), arg) in constructor.args.iter().zip(&arg_types).zip(&new.args) {
+                if parameter.param_type != *arg_type {
+                    self.argument_conversions.insert(argument_value(arg).span, parameter.param_type.clone());
+                }
+            }
+        }
+        Ok(Type::Pointer(Box::new(Type::Class(new.class_name.clone(), type_args))))
+    }
+    fn type_check_field(&mut self, span: Span, class_name: &str, type_args: &[Type], name: &str) -> Result<Type, Diagnostic> {
+        let field: ClassVariable = match self.find_field(class_name, name) {
+            Some(field) => field,
+            None => return Err(Diagnostic::error(codes::UNKNOWN_MEMBER, span, format!("no field named `{}` on class `{}`", name, class_name))),
+        };
+        if field.access == AccessModifier::Private && self.current_class.as_deref() != Some(class_name) {
+            return Err(Diagnostic::error(codes::PRIVATE_MEMBER, span, format!("field `{}` of class `{}` is private", name, class_name)));
+        }
+        Ok(field.var_type.substitute(&self.classes[class_name].type_params, type_args))
+    }
+    fn type_check_assignment(&mut self, span: Span, target: &Expr, op: &TokenKind, value: &Expr) -> Result<Type, Diagnostic> {
+        self.type_check_assignable(target)?;
+        let target_type: Type = self.type_checker_get_type(target)?;
+        let value_type: Type = match op {
+            TokenKind::PlusEqual => self.type_check_binary_op(span, target, &TokenKind::Plus, value)?,
+            TokenKind::MinusEqual => self.type_check_binary_op(span, target, &TokenKind::Minus, value)?,
+            TokenKind::StarEqual => self.type_check_binary_op(span, target, &TokenKind::Star, value)?,
+            TokenKind::SlashEqual => self.type_check_binary_op(span, target, &TokenKind::Slash, value)?,
+            _ => self.type_checker_get_type_expecting(value, &target_type)?,
+        };
+        self.type_check_compatible(value.span, &target_type, &value_type)?;
+        Ok(target_type)
+    }
+    /// Only variables, `mutable` parameters and fields can be assigned to, and
+    /// constants only by their declaration.
+    fn type_check_assignable(&mut self, target: &Expr) -> Result<(), Diagnostic> {
+        match &target.kind {
+            ExprKind::Variable(name) => match self.bindings.get(&target.span).map(|symbol| symbol.kind) {
+                Some(SymbolKind::Variable { constant: true }) => {
+                    let declaration: Span = self.bindings[&target.span].span;
+                    Err(Diagnostic::error(codes::ASSIGN_TO_CONSTANT, target.span, format!("cannot assign to constant `{}`", name))
+                        .with_note(format!("`{}` is declared at {}:{}", name, declaration.line, declaration.column)))
+                },
+                Some(SymbolKind::Parameter { mutable: false }) => Err(Diagnostic::error(codes::ASSIGN_TO_CONSTANT, target.span, format!("cannot assign to immutable parameter `{}`", name))
+                    .with_note(format!("declare it as `mutable {}` to allow assigning to it", name))),
+                _ => Ok(()),
+            },
+            // the field of a temporary is not an lvalue, but the field behind a pointer always is
+            ExprKind::Member(parent, _) => match self.type_checker_get_type(parent)? {
+                Type::Array(..) | Type::Slice(_) => Err(Diagnostic::error(codes::INVALID_ASSIGNMENT, target.span, "cannot assign to the length of an array or a slice")),
+                _ => self.type_check_assignable(parent),
+            },
+            // the elements of an array belong to it, but a slice or a pointer refers to elements stored elsewhere
+            ExprKind::Index(sequence, _) => match self.type_checker_get_type(sequence)? {
+                Type::Array(..) => self.type_check_assignable(sequence),
+                _ => Ok(()),
+            },
+            ExprKind::PointerMember(..) => Ok(()),
+            _ => Err(Diagnostic::error(codes::INVALID_ASSIGNMENT, target.span, "invalid left-hand side of assignment")
+                .with_note("only variables, fields and elements can be assigned to")),
+        }
+    }
+    fn type_check_arguments(&mut self, span: Span, name: &str, args: &[Expr], parameters: &[Parameter]) -> Result<(), Diagnostic> {
+        self.type_check_labels(span, name, args, parameters)?;
+        for (arg, parameter) in args.iter().zip(parameters) {
+            let arg_type: Type = self.type_checker_get_type_expecting(argument_value(arg), &parameter.param_type)?;
+            self.type_check_compatible(arg.span, &parameter.param_type, &arg_type)?;
+        }
+        Ok(())
+    }
+    /// Lines the arguments of a call up with the parameters of `name`. Arguments are
+    /// passed by position, or by label to any parameter that isn't `anonymous`;
+    /// labelled arguments come last and in the order of the parameters, so the C++
+    /// call can pass everything by position.
+    fn type_check_labels(&self, span: Span, name: &str, args: &[Expr], parameters: &[Parameter]) -> Result<(), Diagnostic> {
+        let wrong_count = || Diagnostic::error(codes::WRONG_ARGUMENT_COUNT, span, format!("`{}` takes {} argument{} but {} {} supplied", name, parameters.len(), if parameters.len() == 1 { "" } else { "s" }, args.len(), if args.len() == 1 { "was" } else { "were" }));
+        if args.len() > parameters.len() {
+            return Err(wrong_count());
+        }
+        let mut supplied: Vec<bool> = vec![false; parameters.len()];
+        let mut next: usize = 0;
+        let mut labelled: Option<&str> = None;
+        for arg in args {
+            let label: &String = match &arg.kind {
+                ExprKind::NamedArgument(label, _) => label,
+                _ => {
+                    if let Some(previous) = labelled {
+                        return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, "positional argument after a labelled argument")
+                            .with_note(format!("the argument for `{}` is labelled, so the ones after it must be too", previous)));
+                    }
+                    supplied[next] = true;
+                    next += 1;
+                    continue;
+                },
+            };
+            let position: usize = match parameters.iter().position(|parameter| parameter.name == *label) {
+                Some(position) => position,
+                None => return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, format!("`{}` has no parameter named `{}`", name, label))),
+            };
+            if parameters[position].is_anonymous {
+                return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, format!("parameter `{}` of `{}` is anonymous", label, name))
+                    .with_note("anonymous parameters can only be passed by position"));
+            }
+            if supplied[position] {
+                return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, format!("argument for `{}` is passed more than once", label)));
+            }
+            if position < next {
+                return Err(Diagnostic::error(codes::INVALID_LABEL, arg.span, format!("argument for `{}` is out of order", label))
+                    .with_note(format!("labelled arguments must follow the order of the parameters of `{}`", name)));
+            }
+            supplied[position] = true;
+            next = position + 1;
+            labelled = Some(label);
+        }
+        match supplied.iter().position(|supplied| !supplied) {
+            Some(missing) if labelled.is_some() && !parameters[missing].is_anonymous => Err(Diagnostic::error(codes::WRONG_ARGUMENT_COUNT, span, format!("missing argument for `{}`", parameters[missing].name))
+                .with_note(format!("pass it as `{}: ...`", parameters[missing].name))),
+            Some(_) => Err(wrong_count()),
+            None => Ok(()),
+        }
+    }
+    fn type_check_unary_op(&mut self, span: Span, op: &TokenKind, operand: &Expr) -> Result<Type, Diagnostic> {
+        // `-2147483648` is in range even though `2147483648` on its own is not
+        if let (TokenKind::Minus, ExprKind::Int(int, suffix)) = (op, &operand.kind) {
+            let int_type: Type = literal_type(suffix.as_deref(), Type::Int);
+            return self.type_check_int_literal(span, *int, &int_type, true);
+        }
+        let operand_type: Type = self.type_checker_get_type(operand)?;
+        if let Type::Parameter(parameter) = &operand_type {
+            self.use_type_parameter(parameter, TypeParameterUsage::Unary(op.clone()), span);
+            return Ok(operand_type);
+        }
+        unary_op_type(op, &operand_type).ok_or_else(|| Diagnostic::error(codes::INVALID_OPERANDS, span, format!("cannot apply {} to `{}`", op, operand_type)))
+    }
+    fn type_check_int_literal(&mut self, span: Span, int: u64, int_type: &Type, negated: bool) -> Result<Type, Diagnostic> {
+        let (min, max): (i128, i128) = match int_type.integer_range() {
+            Some(range) => range,
+            None => return Err(Diagnostic::error(codes::MISMATCHED_TYPES, span, format!("`{}` is not an integer type", int_type))),
+        };
+        let value: i128 = if negated { -(int as i128) } else { int as i128 };
+        if negated && min == 0 {
+            return Err(Diagnostic::error(codes::INVALID_OPERANDS, span, format!("cannot negate an unsigned `{}` literal", int_type)));
+        }
+        if value < min || value > max {
+            return Err(Diagnostic::error(codes::LITERAL_OUT_OF_RANGE, span, format!("literal out of range for `{}`", int_type))
+                .with_note(format!("the range of `{}` is {}..={}", int_type, min, max)));
+        }
+        Ok(int_type.clone())
+    }
+    /// An integer literal standing for a float must keep its exact value, which
+    /// rules out most integers beyond the 24 bits of a `float`'s mantissa.
+    fn type_check_int_literal_as_float(&mut self, span: Span, int: u64, float_type: &Type, negated: bool) -> Result<Type, Diagnostic> {
+        let value: i128 = if negated { -(int as i128) } else { int as i128 };
+        if !represents_exactly(float_type, value) {
+            let mantissa: u32 = if *float_type == Type::Float { f32::MANTISSA_DIGITS } else { f64::MANTISSA_DIGITS };
+            return Err(Diagnostic::error(codes::LITERAL_OUT_OF_RANGE, span, format!("`{}` cannot hold {} exactly", float_type, value))
+                .with_note(format!("its mantissa has {} bits; write the literal as a float, as in `{}.0`, to accept the rounding", mantissa, value)));
+        }
+        Ok(float_type.clone())
+    }
+    fn type_check_float_literal(&mut self, span: Span, float: f64, float_type: &Type) -> Result<Type, Diagnostic> {
+        if *float_type == Type::Float && (float as f32).is_infinite() {
+            return Err(Diagnostic::error(codes::LITERAL_OUT_OF_RANGE, span, "literal out of range for `float`")
+                .with_note(format!("the largest `float` is {:e}; use `f64` for larger values", f32::MAX)));
+        }
+        if float.is_infinite() {
+            return Err(Diagnostic::error(codes::LITERAL_OUT_OF_RANGE, span, "literal out of range for `f64`")
+                .with_note(format!("the largest `f64` is {:e}", f64::MAX)));
+        }
+        Ok(float_type.clone())
+    }
+    /// The type of `expr` where a value of type `expected` is wanted. Literals
+    /// without a suffix take on a numeric `expected` type when their value fits,
+    /// so `var x: u8 = 200;` and `x + 1` need no suffix.
+    fn type_checker_get_type_expecting(&mut self, expr: &Expr, expected: &Type) -> Result<Type, Diagnostic> {
+        match (unsuffixed_int_literal(expr), &expr.kind) {
+            (Some((int, negated)), _) if expected.is_integer() => self.type_check_int_literal(expr.span, int, expected, negated),
+            (Some((int, negated)), _) if expected.is_float() => self.type_check_int_literal_as_float(expr.span, int, expected, negated),
+            (_, ExprKind::Float(float, None)) if expected.is_float() => self.type_check_float_literal(expr.span, *float, expected),
+            (_, ExprKind::Array(elements)) => match expected {
+                Type::Array(element_type, _) => self.type_check_array(expr.span, elements, Some(element_type)),
+                _ => self.type_checker_get_type(expr),
+            },
+            // where a class value is wanted, `new` constructs the value itself instead of an object behind a pointer
+            (_, ExprKind::New(new)) if matches!(expected, Type::Class(..)) => match self.type_check_new(expr.span, new)? {
+                Type::Pointer(value_type) => {
+                    self.expr_types.insert(expr.span, (*value_type).clone());
+                    Ok(*value_type)
+                },
+                found => Ok(found),
+            },
+            (_, ExprKind::ArrayRepeat(value, length)) => match expected {
+                Type::Array(element_type, _) => self.type_check_array_repeat(expr.span, value, *length, Some(element_type)),
+                _ => self.type_checker_get_type(expr),
+            },
+            _ => match (expected, self.type_checker_get_type(expr)?) {
+                (Type::Slice(element_type), Type::Array(found_type, _)) if **element_type == *found_type => {
+                    // a slice can change the elements it views, so it can't view a constant or a temporary
+                    self.type_check_assignable(expr).map_err(|_| Diagnostic::error(codes::MISMATCHED_TYPES, expr.span, format!("cannot make a `{}` slice of this array", expected))
+                        .with_note("slices can only view arrays that can be assigned to, such as a `var`"))?;
+                    Ok(expected.clone())
+                },
+                (_, found) => Ok(found),
+            },
+        }
+    }
+    /// The type both operands of an arithmetic or comparison operator convert to,
+    /// if one of them widens to the other.
+    fn numeric_common_type(&self, left: &Type, right: &Type) -> Option<Type> {
+        if !left.is_numeric() || !right.is_numeric() {
+            return None;
+        }
+        if self.type_is_compatible(left, right) {
+            Some(left.clone())
+        } else if self.type_is_compatible(right, left) {
+            Some(right.clone())
+        } else {
+            None
+        }
+    }
+    /// The elements of an array literal all have the type of the array, or when it
+    /// isn't known from the context, the type of the first element.
+    fn type_check_array(&mut self, span: Span, elements: &[Expr], element_type: Option<&Type>) -> Result<Type, Diagnostic> {
+        let element_type: Type = match (element_type, elements.first()) {
+            (Some(element_type), _) => element_type.clone(),
+            (None, Some(first)) => self.type_checker_get_type(first)?,
+            (None, None) => return Err(Diagnostic::error(codes::MISMATCHED_TYPES, span, "cannot infer the element type of an empty array")
+                .with_note("give the array a type: `var values: [int; 0] = [];`")),
+        };
+        for element in elements {
+            let found: Type = self.type_checker_get_type_expecting(element, &element_type)?;
+            self.type_check_compatible(element.span, &element_type, &found)?;
+            self.expr_types.insert(element.span, found);
+        }
+        let array_type: Type = Type::Array(Box::new(element_type), elements.len() as u64);
+        self.expr_types.insert(span, array_type.clone());
+        Ok(array_type)
+    }
+    fn type_check_array_repeat(&mut self, span: Span, value: &Expr, length: u64, element_type: Option<&Type>) -> Result<Type, Diagnostic> {
+        let element_type: Type = match element_type {
+            Some(element_type) => {
+                let found: Type = self.type_checker_get_type_expecting(value, element_type)?;
+                self.type_check_compatible(value.span, element_type, &found)?;
+                element_type.clone()
+            },
+            None => self.type_checker_get_type(value)?,
+        };
+        let array_type: Type = Type::Array(Box::new(element_type), length);
+        self.expr_types.insert(span, array_type.clone());
+        Ok(array_type)
+    }
+    /// Indexes are integers; a literal index into an array is also checked against its length.
+    fn type_check_index(&mut self, sequence: &Expr, position: &Expr) -> Result<Type, Diagnostic> {
+        let sequence_type: Type = self.type_checker_get_type(sequence)?;
+        let element_type: Type = match &sequence_type {
+            Type::Array(inner, _) | Type::Slice(inner) | Type::Pointer(inner) => (**inner).clone(),
+            _ => return Err(Diagnostic::error(codes::INVALID_OPERANDS, sequence.span, format!("cannot index into a value of type `{}`", sequence_type))),
+        };
+        let position_type: Type = self.type_checker_get_type_expecting(position, &Type::Usize)?;
+        if !position_type.is_integer() {
+            return Err(Diagnostic::error(codes::MISMATCHED_TYPES, position.span, format!("an index must be an integer, found `{}`", position_type)));
+        }
+        if let (Type::Array(_, length), ExprKind::Int(int, _)) = (&sequence_type, &position.kind) {
+            if int >= length {
+                return Err(Diagnostic::error(codes::INDEX_OUT_OF_BOUNDS, position.span, format!("index {} is out of bounds for `{}`", int, sequence_type))
+                    .with_note(format!("the indexes of `{}` are 0..{}", sequence_type, length)));
+            }
+        }
+        self.expr_types.insert(sequence.span, sequence_type);
+        Ok(element_type)
+    }
+    /// Numbers convert to each other, integers to and from `char`, `bool` to
+    /// integers, and class pointers to pointers to their base classes.
+    fn type_check_cast(&mut self, span: Span, value: &Expr, target: &Type) -> Result<Type, Diagnostic> {
+        self.type_check_type(span, target)?;
+        // a literal that fits is typed as the target, so `5000000000 as i64` doesn't overflow an `int` first
+        let value_type: Type = if literal_fits(value, target) { self.type_checker_get_type_expecting(value, target)? } else { self.type_checker_get_type(value)? };
+        let allowed: bool = match (&value_type, target) {
+            _ if value_type == *target => true,
+            _ if value_type.is_numeric() && target.is_numeric() => true,
+            (Type::Char | Type::Bool, _) => target.is_integer(),
+            (_, Type::Char) => value_type.is_integer(),
+            (Type::Pointer(_) | Type::Null, Type::Pointer(_)) => self.type_is_compatible(target, &value_type),
+            _ => false,
+        };
+        if allowed {
+            return Ok(target.clone());
+        }
+        let error: Diagnostic = Diagnostic::error(codes::INVALID_CAST, span, format!("cannot cast `{}` as `{}`", value_type, target));
+        Err(match (&value_type, target) {
+            (Type::Pointer(found), Type::Pointer(expected)) => match (&**found, &**expected) {
+                (Type::Class(found, _), Type::Class(expected, _)) if self.is_subclass(expected, found) => {
+                    error.with_note(format!("`{}` derives from `{}`; use `downcast<{}>(...)` to check the type of the object", expected, found, expected))
+                },
+                _ => error,
+            },
+            (_, Type::Bool) if value_type.is_numeric() => error.with_note("compare with zero instead: `value != 0`"),
+            _ => error,
+        })
+    }
+    fn type_check_downcast(&mut self, span: Span, class_name: &str, value: &Expr) -> Result<Type, Diagnostic> {
+        match self.classes.get(class_name) {
+            Some(class) if !class.type_params.is_empty() => return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("cannot downcast to generic class `{}`", class_name))),
+            Some(_) => {},
+            None if self.interfaces.contains_key(class_name) => return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("cannot downcast to interface `{}`", class_name))
+                .with_note(format!("downcast to a class implementing `{}` and convert that with `as {}*`", class_name, class_name))),
+            None => return Err(Diagnostic::error(codes::UNKNOWN_CLASS, span, format!("unknown class `{}`", class_name))),
+        }
+        let value_type: Type = self.type_checker_get_type(value)?;
+        let source: String = match &value_type {
+            Type::Pointer(inner) => match &**inner {
+                Type::Class(source, _) => source.clone(),
+                _ => return Err(Diagnostic::error(codes::INVALID_CAST, value.span, format!("`downcast` needs a pointer to a class, found `{}`", value_type))),
+            },
+            _ => return Err(Diagnostic::error(codes::INVALID_CAST, value.span, format!("`downcast` needs a pointer to a class, found `{}`", value_type))),
+        };
+        if source == class_name {
+            return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("`{}` is already a `{}`", value_type, class_name)));
+        }
+        if self.is_subclass(&source, class_name) {
+            return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("`{}` is already a `{}`", value_type, class_name))
+                .with_note(format!("use `as {}*` to convert to a base class", class_name)));
+        }
+        if !self.is_subclass(class_name, &source) {
+            return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("cannot downcast `{}` to `{}`, which does not derive from `{}`", value_type, class_name, source)));
+        }
+        if !self.is_polymorphic(&source) {
+            return Err(Diagnostic::error(codes::INVALID_CAST, span, format!("cannot downcast from `{}`, which has no virtual methods", source))
+                .with_note("the type of an object is only known at run time when its class or a base class has a virtual method"));
+        }
+        Ok(Type::Pointer(Box::new(Type::Class(class_name.to_string(), vec![]))))
+    }
+    fn type_check_binary_op(&mut self, span: Span, left: &Expr, op: &TokenKind, right: &Expr) -> Result<Type, Diagnostic> {
+        // a literal operand takes the type of the other operand: `x + 1` for a `u8` x is a `u8`
+        let (left_type, right_type): (Type, Type) = match (unsuffixed_int_literal(left).is_some(), unsuffixed_int_literal(right).is_some()) {
+            (true, false) => {
+                let right_type: Type = self.type_checker_get_type(right)?;
+                (self.type_checker_get_type_expecting(left, &right_type)?, right_type)
+            },
+            (false, true) => {
+                let left_type: Type = self.type_checker_get_type(left)?;
+                let right_type: Type = self.type_checker_get_type_expecting(right, &left_type)?;
+                (left_type, right_type)
+            },
+            _ => (self.type_checker_get_type(left)?, self.type_checker_get_type(right)?),
+        };
+        // whether a type argument supports the operator is checked where the generic is used
+        if let (Type::Parameter(parameter), Type::Parameter(right_parameter)) = (&left_type, &right_type) {
+            if parameter == right_parameter && !matches!(op, TokenKind::AndAnd | TokenKind::PipePipe) {
+                self.use_type_parameter(parameter, TypeParameterUsage::Binary(op.clone()), span);
+                let comparison: bool = matches!(op, TokenKind::EqualEqual | TokenKind::BangEqual | TokenKind::Less | TokenKind::LessEqual | TokenKind::Greater | TokenKind::GreaterEqual);
+                return Ok(if comparison { Type::Bool } else { left_type });
+            }
+        }
+        self.binary_op_type(&left_type, op, &right_type)
+            .ok_or_else(|| Diagnostic::error(codes::INVALID_OPERANDS, span, format!("cannot apply {} to `{}` and `{}`", op, left_type, right_type)))
+    }
+    /// The type of `left op right`, if the operator applies to those operands.
+    fn binary_op_type(&self, left_type: &Type, op: &TokenKind, right_type: &Type) -> Option<Type> {
+        let common: Option<Type> = self.numeric_common_type(left_type, right_type);
+        match op {
+            TokenKind::Plus | TokenKind::Minus | TokenKind::Star | TokenKind::Slash => match (left_type, right_type) {
+                (Type::String, Type::String) if *op == TokenKind::Plus => Some(Type::String),
+                _ => common,
+            },
+            TokenKind::Percent => common.filter(|common| common.is_integer()),
+            // the result of a shift has the type of the value being shifted
+            TokenKind::ShiftLeft | TokenKind::ShiftRight => (left_type.is_integer() && right_type.is_integer()).then(|| left_type.clone()),
+            TokenKind::Ampersand | TokenKind::Pipe | TokenKind::Caret => match (left_type, right_type) {
+                (Type::Bool, Type::Bool) => Some(Type::Bool),
+                _ => common.filter(|common| common.is_integer()),
+            },
+            TokenKind::AndAnd | TokenKind::PipePipe => match (left_type, right_type) {
+                (Type::Bool, Type::Bool) => Some(Type::Bool),
+                _ => None,
+            },
+            TokenKind::EqualEqual | TokenKind::BangEqual => {
+                let comparable: bool = common.is_some()
+                    || (!left_type.is_numeric() && !matches!(left_type, Type::Void | Type::Class(..) | Type::Slice(_)) && (self.type_is_compatible(left_type, right_type) || self.type_is_compatible(right_type, left_type)));
+                comparable.then_some(Type::Bool)
+            },
+            TokenKind::Less | TokenKind::LessEqual | TokenKind::Greater | TokenKind::GreaterEqual => {
+                let ordered: bool = common.is_some()
+                    || (left_type == right_type && matches!(left_type, Type::Char | Type::String));
+                ordered.then_some(Type::Bool)
+            },
+            _ => None,
+        }
+    }
+    fn find_field(&self, class_name: &str, name: &str) -> Option<ClassVariable> {
+        let class: &Class = self.classes.get(class_name)?;
+        for member in &class.methods {
+            if let ExprKind::ClassVariable(class_variable) = &member.kind {
+                if class_variable.name == name {
+                    return Some(class_variable.clone());
+                }
+            }
+        }
+        self.find_field(class.base_class.as_deref()?, name)
+    }
+    /// The constructors `new` can call: the declared ones, or, for a class that
+    /// declares none, the generated one taking the public fields without a default
+    /// in order, by label for the `named` ones.
+    fn find_constructors(&self, class_name: &str) -> Vec<Constructor> {
+        let class: &Class = match self.classes.get(class_name) {
+            Some(class) => class,
+            None => return vec![],
+        };
+        let constructors: Vec<Constructor> = class.methods.iter().filter_map(|member| match &member.kind {
+            ExprKind::Constructor(constructor) => Some(constructor.clone()),
+            _ => None,
+        }).collect();
+        if !constructors.is_empty() {
+            return constructors;
+        }
+        let args: Vec<Parameter> = class.methods.iter().filter_map(|member| match &member.kind {
+            ExprKind::ClassVariable(class_variable) if class_variable.access == AccessModifier::Public && matches!(class_variable.initializer.kind, ExprKind::Empty) => Some(Parameter {
+                name: class_variable.name.clone(),
+                param_type: class_variable.var_type.clone(),
+                is_anonymous: !class_variable.is_named,
+                is_mutable: false,
+            }),
+            _ => None,
+        }).collect();
+        vec![Constructor { doc: None, args, body: Box::new(Expr::new(ExprKind::Empty, Span::default())), access: AccessModifier::Public }]
+    }
+    fn is_polymorphic(&self, class_name: &str) -> bool {
+        if self.interfaces.contains_key(class_name) {
+            return true;
+        }
+        let mut current: Option<&Class> = self.classes.get(class_name);
+        while let Some(class) = current {
+            if !class.interfaces.is_empty() || class.methods.iter().any(|member| matches!(&member.kind, ExprKind::ClassFunction(class_function) if class_function.is_virtual)) {
+                return true;
+            }
+            current = class.base_class.as_ref().and_then(|base_class| self.classes.get(base_class));
+        }
+        false
+    }
+    /// Whether the method `name` of `class_name` or one of its bases is virtual,
+    /// which includes the methods implementing an interface method.
+    fn is_overridable(&self, class_name: &str, name: &str) -> bool {
+        if self.interfaces.contains_key(class_name) {
+            return self.find_method(class_name, name).is_some();
+        }
+        let class: &Class = match self.classes.get(class_name) {
+            Some(class) => class,
+            None => return false,
+        };
+        let declared: bool = class.methods.iter().any(|member| matches!(&member.kind, ExprKind::ClassFunction(class_function) if class_function.name == name && (class_function.is_virtual || class_function.is_override)));
+        declared || class.base_class.iter().chain(&class.interfaces).any(|base| self.is_overridable(base, name))
+    }
+    /// The method `name` of a class, declared in it or inherited, or of an interface.
+    fn find_method(&self, class_name: &str, name: &str) -> Option<ClassFunction> {
+        let class: &Class = match self.classes.get(class_name) {
+            Some(class) => class,
+            None => return self.interfaces.get(class_name)?.methods.iter().find_map(|member| match &member.kind {
+                ExprKind::ClassFunction(class_function) if class_function.name == name => Some(class_function.clone()),
+                _ => None,
+            }),
+        };
+        for member in &class.methods {
+            if let ExprKind::ClassFunction(class_function) = &member.kind {
+                if class_function.name == name {
+                    return Some(class_function.clone());
+                }
+            }
+        }
+        self.find_method(class.base_class.as_deref()?, name)
+    }
+}
+
+/// The type named by a literal's suffix, or `default` for a literal without one.
+fn literal_type(suffix: Option<&str>, default: Type) -> Type {
+    suffix.and_then(Type::from_name).unwrap_or(default)
+}
+
+/// The value of an integer literal without a suffix, and whether it is negated.
+fn unsuffixed_int_literal(expr: &Expr) -> Option<(u64, bool)> {
+    match &expr.kind {
+        ExprKind::Int(int, None) => Some((*int, false)),
+        ExprKind::UnaryOp(TokenKind::Minus, operand) => match operand.kind {
+            ExprKind::Int(int, None) => Some((int, true)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `expr` is a literal without a suffix that can take on `expected`.
+fn literal_fits(expr: &Expr, expected: &Type) -> bool {
+    match (unsuffixed_int_literal(expr), expected.integer_range()) {
+        (Some((int, negated)), Some((min, max))) => {
+            let value: i128 = if negated { -(int as i128) } else { int as i128 };
+            min <= value && value <= max
+        },
+        (Some((int, negated)), None) => expected.is_float() && represents_exactly(expected, if negated { -(int as i128) } else { int as i128 }),
+        _ => false,
+    }
+}
+
+/// Whether the float type `float_type` holds the integer `value` without rounding it.
+fn represents_exactly(float_type: &Type, value: i128) -> bool {
+    match float_type {
+        Type::Float => value as f32 as i128 == value,
+        _ => value as f64 as i128 == value,
+    }
+}
+
+/// How a constructor is named in diagnostics, e.g. `Point(int, int)`.
+fn constructor_signature(class_name: &str, args: &[Parameter]) -> String {
+    let types: Vec<String> = args.iter().map(|arg| arg.param_type.to_string()).collect();
+    format!("{}({})", class_name, types.join(", "))
+}
+
+/// The value passed by an argument, without its label.
+pub(crate) fn argument_value(arg: &Expr) -> &Expr {
+    match &arg.kind {
+        ExprKind::NamedArgument(_, value) => value,
+        _ => arg,
+    }
+}
+
+/// Types whose values are left indeterminate in C++ when nothing initializes them.
+/// A type parameter might stand for one of them.
+fn is_scalar(type_: &Type) -> bool {
+    match type_ {
+        Type::Array(inner, _) => is_scalar(inner),
+        _ => type_.is_numeric() || matches!(type_, Type::Bool | Type::Char | Type::Pointer(_) | Type::Parameter(_)),
+    }
+}
+
+fn is_printable(type_: &Type) -> bool {
+    !matches!(type_, Type::Void | Type::Class(..) | Type::Parameter(_) | Type::Null | Type::Array(..) | Type::Slice(_))
+}
+
+/// The type of `op operand`, if the operator applies to the operand.
+fn unary_op_type(op: &TokenKind, operand_type: &Type) -> Option<Type> {
+    match (op, operand_type) {
+        (TokenKind::Bang, Type::Bool) => Some(Type::Bool),
+        (TokenKind::Minus, _) if operand_type.is_float() || operand_type.integer_range().is_some_and(|(min, _)| min < 0) => Some(operand_type.clone()),
+        (TokenKind::Tilde, _) if operand_type.is_integer() => Some(operand_type.clone()),
+        _ => None,
+    }
+}
+
+fn substitute_parameters(parameters: &[Parameter], type_params: &[String], type_args: &[Type]) -> Vec<Parameter> {
+    parameters.iter().map(|parameter| Parameter { param_type: parameter.param_type.substitute(type_params, type_args), ..parameter.clone() }).collect()
+}
+
+/// Binds the type parameters in `parameter_type` to the matching parts of
+/// `arg_type`, keeping the first binding of each.
+fn unify(parameter_type: &Type, arg_type: &Type, type_params: &[String], inferred: &mut [Option<Type>]) {
+    match (parameter_type, arg_type) {
+        (Type::Parameter(name), _) => {
+            if let Some(position) = type_params.iter().position(|type_param| type_param == name) {
+                if inferred[position].is_none() && *arg_type != Type::Null {
+                    inferred[position] = Some(arg_type.clone());
+                }
+            }
+        },
+        (Type::Pointer(parameter_inner), Type::Pointer(arg_inner))
+            | (Type::Array(parameter_inner, _), Type::Array(arg_inner, _))
+            | (Type::Slice(parameter_inner), Type::Slice(arg_inner) | Type::Array(arg_inner, _)) => unify(parameter_inner, arg_inner, type_params, inferred),
+        (Type::Class(parameter_class, parameter_args), Type::Class(arg_class, arg_args)) if parameter_class == arg_class => {
+            for (parameter_arg, arg_arg) in parameter_args.iter().zip(arg_args) {
+                unify(parameter_arg, arg_arg, type_params, inferred);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// Whether running `expr` always ends in a `return`, so control never falls off
+/// the end of a function.
+fn always_returns(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Return(_) => true,
+        ExprKind::Block(block) => block.exprs.iter().any(always_returns),
+        ExprKind::If(if_expr) => match &if_expr.else_branch {
+            Some(else_branch) => always_returns(&if_expr.then_branch) && always_returns(else_branch),
+            None => false,
+        },
+        // an endless loop only ends through a `return` or a `break`
+        ExprKind::While(while_expr) => matches!(while_expr.condition.kind, ExprKind::Bool(true)) && !breaks_out(&while_expr.body),
+        ExprKind::For(for_expr) => matches!(for_expr.condition.kind, ExprKind::Empty | ExprKind::Bool(true)) && !breaks_out(&for_expr.body),
+        _ => false,
+    }
+}
+
+/// Whether `expr` contains a `break` out of the loop it is the body of.
+fn breaks_out(expr: &Expr) -> bool {
+    match &expr.kind {
+        ExprKind::Break => true,
+        ExprKind::Block(block) => block.exprs.iter().any(breaks_out),
+        ExprKind::If(if_expr) => breaks_out(&if_expr.then_branch) || if_expr.else_branch.as_deref().is_some_and(breaks_out),
+        // a `break` in a nested loop leaves that loop instead
+        _ => false,
+    }
+}
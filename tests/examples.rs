@@ -0,0 +1,143 @@
+//! Runs every stage of the compiler, from lexing to C++ generation, on the
+//! examples and on small programs that must compile or must be rejected.
+
+use std::path::{Path, PathBuf};
+
+use gemstone::ast::Expr;
+use gemstone::diagnostics::Diagnostic;
+use gemstone::lexer::Token;
+use gemstone::Gemstone;
+
+/// Examples that exist to show an error, with the code they are rejected with.
+const REJECTED_EXAMPLES: &[(&str, &str)] = &[
+    ("examples/typechecker/error.gem", "E0200"),
+    ("examples/types/pointer_pointer_pointer_pointer.gem", "E0200"),
+];
+
+fn compile(name: &str, source: &str, monomorphise: bool) -> Result<String, Vec<Diagnostic>> {
+    let mut gemstone: Gemstone = Gemstone::new();
+    gemstone.set_monomorphise(monomorphise);
+    let file: usize = gemstone.add_file(name, source);
+    let tokens: Vec<Token> = gemstone.lex(file)?;
+    let exprs: Vec<Expr> = gemstone.parse(&tokens)?;
+    gemstone.type_checker(&exprs)?;
+    gemstone.compile(&exprs)
+}
+
+fn codes(diagnostics: &[Diagnostic]) -> Vec<&'static str> {
+    diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).map(|diagnostic| diagnostic.code).collect()
+}
+
+fn gem_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    let entries: std::fs::ReadDir = std::fs::read_dir(directory).unwrap_or_else(|error| panic!("failed to read {}: {}", directory.display(), error));
+    for entry in entries {
+        let path: PathBuf = entry.expect("failed to read a directory entry").path();
+        if path.is_dir() {
+            gem_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "gem") {
+            files.push(path);
+        }
+    }
+}
+
+/// Compiles `source` in both modes, failing with the error codes if either is rejected.
+fn assert_compiles(source: &str) -> String {
+    let output: String = compile("test.gem", source, false).unwrap_or_else(|diagnostics| panic!("expected to compile, got {:?}", codes(&diagnostics)));
+    if let Err(diagnostics) = compile("test.gem", source, true) {
+        panic!("expected to compile with --monomorphise, got {:?}", codes(&diagnostics));
+    }
+    output
+}
+
+fn assert_rejected(source: &str, code: &str) {
+    match compile("test.gem", source, false) {
+        Ok(_) => panic!("expected {} for:\n{}", code, source),
+        Err(diagnostics) => assert!(codes(&diagnostics).contains(&code), "expected {}, got {:?} for:\n{}", code, codes(&diagnostics), source),
+    }
+}
+
+#[test]
+fn examples_and_concepts_compile() {
+    let root: &Path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files: Vec<PathBuf> = vec![];
+    gem_files(&root.join("examples"), &mut files);
+    gem_files(&root.join("concepts"), &mut files);
+    assert!(!files.is_empty(), "no examples found");
+    for path in files {
+        let name: String = path.strip_prefix(root).expect("example outside of the crate").display().to_string();
+        let source: String = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("failed to read {}: {}", name, error));
+        for monomorphise in [false, true] {
+            let result: Result<String, Vec<Diagnostic>> = compile(&name, &source, monomorphise);
+            match (REJECTED_EXAMPLES.iter().find(|(rejected, _)| *rejected == name), result) {
+                (None, Ok(_)) => {},
+                (None, Err(diagnostics)) => panic!("{} failed to compile: {:?}", name, codes(&diagnostics)),
+                (Some((_, code)), Ok(_)) => panic!("{} should be rejected with {}", name, code),
+                (Some((_, code)), Err(diagnostics)) => assert_eq!(codes(&diagnostics), vec![*code], "{}", name),
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_invalid_literals() {
+    assert_rejected("function main() -> int {\n    var d: f64 = 1e400;\n    return 0;\n}\n", "E0213");
+    assert_rejected("function main() -> int {\n    var f: float = 16777217;\n    return 0;\n}\n", "E0213");
+    assert_rejected("function main() -> int {\n    println(\"A\\u{42\");\n    return 0;\n}\n", "E0005");
+}
+
+#[test]
+fn rejects_lossy_integer_to_float_conversions() {
+    assert_rejected("function main() -> int {\n    var big: i64 = 5;\n    var g: float = big;\n    return 0;\n}\n", "E0200");
+    assert_rejected("function main() -> int {\n    var n: int = 5;\n    var g: float = n;\n    return 0;\n}\n", "E0200");
+    assert_compiles("function main() -> int {\n    var small: i16 = 5;\n    var n: int = 5;\n    var a: float = small;\n    var b: f64 = n;\n    return 0;\n}\n");
+}
+
+#[test]
+fn rejects_functions_missing_a_return() {
+    assert_rejected("function f(x: int) -> int {\n    if x > 0 {\n        return 1;\n    }\n}\nfunction main() -> int {\n    return f(1);\n}\n", "E0200");
+    assert_compiles("function f(x: int) -> int {\n    while true {\n        if x > 0 {\n            return 1;\n        }\n    }\n}\nfunction main() -> int {\n    return f(1);\n}\n");
+}
+
+#[test]
+fn rejects_missing_commas() {
+    assert_rejected("function add(a: int, b: int) -> int {\n    return a + b;\n}\nfunction main() -> int {\n    return add(1 2);\n}\n", "E0100");
+    assert_rejected("function add(a: int b: int) -> int {\n    return a + b;\n}\nfunction main() -> int {\n    return add(1, 2);\n}\n", "E0100");
+}
+
+#[test]
+fn range_loops_take_the_type_of_their_bounds() {
+    let output: String = assert_compiles("function main() -> int {\n    const values: [int; 2] = [1, 2];\n    for i in 0..values.length {\n        println(values[i]);\n    }\n    return 0;\n}\n");
+    assert!(output.contains("for (std::size_t i = 0"), "{}", output);
+    assert_rejected("function main() -> int {\n    const values: [int; 2] = [1, 2];\n    var start: int = 0;\n    for i in start..values.length {\n    }\n    return 0;\n}\n", "E0200");
+}
+
+#[test]
+fn string_literals_become_std_strings() {
+    let output: String = assert_compiles("function main() -> int {\n    println(\"b\" < \"a\");\n    var s: string = \"a\" + \"b\";\n    var t: string = \"a\\0b\";\n    return 0;\n}\n");
+    assert!(output.contains("std::string(\"b\") < std::string(\"a\")"), "{}", output);
+    assert!(output.contains("std::string(\"a\") + std::string(\"b\")"), "{}", output);
+    assert!(output.contains("std::string(\"a\\000b\", 3)"), "{}", output);
+}
+
+#[test]
+fn overloaded_constructors_convert_their_arguments() {
+    let output: String = assert_compiles("class A {\n    public constructor(x: i64) {}\n    public constructor(x: float) {}\n}\nfunction main() -> int {\n    var n: int = 3;\n    const a: A* = new A(n);\n    return 0;\n}\n");
+    assert!(output.contains("new A(static_cast<int64_t>(n))"), "{}", output);
+}
+
+#[test]
+fn methods_call_their_class_by_name() {
+    assert_compiles("class C {\n    public constructor() {}\n    public function one() -> int {\n        return 1;\n    }\n    public function two() -> int {\n        return one() + 1;\n    }\n    public external function three() -> int;\n}\nfunction C::three() {\n    return two() + one();\n}\nfunction main() -> int {\n    const c: C* = new C();\n    return c->three();\n}\n");
+    assert_rejected("class P {\n    public var x: int;\n    public function get() -> int {\n        return x;\n    }\n}\nfunction main() -> int {\n    const p: P* = new P(1);\n    return p.get();\n}\n", "E0205");
+}
+
+#[test]
+fn inherited_methods_implement_interfaces() {
+    let output: String = assert_compiles("interface Named {\n    function name() -> string;\n}\nclass Base {\n    public constructor() {}\n    public function name() -> string {\n        return \"base\";\n    }\n}\nclass Square: Base implements Named {\n    public constructor() {}\n}\nfunction main() -> int {\n    const named: Named* = new Square();\n    println(named->name());\n    return 0;\n}\n");
+    assert!(output.contains("override { return Base::name(); }"), "{}", output);
+}
+
+#[test]
+fn trailing_doc_comments_only_warn() {
+    assert_compiles("interface I {\n    function f() -> int;\n    /// trailing\n}\nclass C implements I {\n    public constructor() {}\n    public function f() -> int {\n        return 1;\n    }\n    /// trailing\n}\nfunction main() -> int {\n    return 0;\n}\n");
+}